@@ -9,6 +9,41 @@
 #error "Wrong board_name, the arduino IDE did not expect a teensy_lc"
 #endif
 
+#elif defined(TEENSY_32)
+#if !defined(TEENSYDUINO) || !defined(__MK20DX256__)
+#error "Wrong board_name, the arduino IDE did not expect a teensy_32"
+#endif
+
+#elif defined(TEENSY_35)
+#if !defined(TEENSYDUINO) || !defined(__MK64FX512__)
+#error "Wrong board_name, the arduino IDE did not expect a teensy_35"
+#endif
+
+#elif defined(TEENSY_36)
+#if !defined(TEENSYDUINO) || !defined(__MK66FX1M0__)
+#error "Wrong board_name, the arduino IDE did not expect a teensy_36"
+#endif
+
+#elif defined(TEENSY_40)
+#if !defined(TEENSYDUINO) || !defined(ARDUINO_TEENSY40)
+#error "Wrong board_name, the arduino IDE did not expect a teensy_40"
+#endif
+
+#elif defined(TEENSY_41)
+#if !defined(TEENSYDUINO) || !defined(ARDUINO_TEENSY41)
+#error "Wrong board_name, the arduino IDE did not expect a teensy_41"
+#endif
+
+#elif defined(PRO_MICRO)
+#if !defined(ARDUINO_AVR_PROMICRO)
+#error "Wrong board_name, the arduino IDE did not expect a pro_micro"
+#endif
+
+#elif defined(RASPBERRY_PI_PICO)
+#if !defined(ARDUINO_RASPBERRY_PI_PICO)
+#error "Wrong board_name, the arduino IDE did not expect a raspberry_pi_pico"
+#endif
+
 #else
 #error "Unknown board_name"
 #endif
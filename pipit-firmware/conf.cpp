@@ -29,6 +29,20 @@ uint8_t decodeSequence(BitSlice<uint8_t> seq_bits, Key* keys_out) {
       return 0;  // Fail
     }
 
+#ifdef USE_FRAGMENTS
+    if (entry->is_fragment) {
+      // Fragments contain whole keypresses, which are decoded in place.
+      const Fragment& fragment = conf::fragment_lookup[entry->key_code];
+      BitSlice<uint8_t> fragment_bits(conf::fragment_bits.data(),
+                                      fragment.start_bit, fragment.num_bits);
+      uint8_t num_keys = decodeSequence(fragment_bits, keys_out + key_index);
+      if (num_keys == 0) {
+        return 0;  // Fail
+      }
+      key_index += num_keys;
+      continue;
+    }
+#endif
     if (entry->is_mod) {
       // Add a mod, and wait for more keys/mods to be added to this keypress in
       // the next iteration.
@@ -107,14 +107,25 @@ struct ModeStruct {
 struct HuffmanChar {
   HuffmanBits bits;
   uint8_t num_bits;
+  // For fragments, this is the index in the fragment lookup.
   uint8_t key_code;
   bool is_mod;
+#ifdef USE_FRAGMENTS
+  bool is_fragment;
+#endif
 
   constexpr BitSlice<uint8_t> code_bits() const {
     return bits.slice_to(num_bits);
   }
 };
 
+// A run of keypresses that's shared between many sequences, stored in the
+// fragment bits.
+struct Fragment {
+  uint16_t start_bit;
+  uint16_t num_bits;
+};
+
 enum class WordSpacePosition {
   Before,
   After,
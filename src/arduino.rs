@@ -1,10 +1,14 @@
+use std::path::PathBuf;
 use std::process::Command;
 
 use error::{Error, ResultExt};
-use types::BoardName;
+use types::{ArduinoBackend, BoardName, UserOptions};
 
-pub struct ArduinoIDE {
+pub struct Arduino {
     board: BoardName,
+    backend: ArduinoBackend,
+    executable: Option<PathBuf>,
+    sketch_directory: PathBuf,
 }
 
 enum ArduinoCommand {
@@ -13,7 +17,7 @@ enum ArduinoCommand {
 }
 
 impl ArduinoCommand {
-    fn args(&self) -> Vec<String> {
+    fn ide_args(&self) -> Vec<String> {
         match self {
             ArduinoCommand::Verify => vec!["--verify".to_owned()],
             ArduinoCommand::Upload { ref port } => {
@@ -26,12 +30,50 @@ impl ArduinoCommand {
             }
         }
     }
+
+    /// Uploading with `arduino-cli` also compiles first, like the IDE does.
+    fn cli_args(&self) -> Vec<String> {
+        let mut v = vec!["compile".to_owned()];
+        if let ArduinoCommand::Upload { ref port } = self {
+            v.push("--upload".to_owned());
+            if let Some(port) = port {
+                v.push("--port".to_owned());
+                v.push(port.to_owned());
+            }
+        }
+        v
+    }
 }
 
-impl ArduinoIDE {
-    pub fn new(board: BoardName) -> Self {
-        Self { board }
+impl Arduino {
+    /// Use the board, backend, executable and sketch from the settings file.
+    pub fn new(options: &UserOptions) -> Self {
+        Self {
+            board: options.board_name,
+            backend: options.arduino_backend,
+            executable: options.arduino_executable.clone(),
+            sketch_directory: options
+                .sketch_directory
+                .clone()
+                .unwrap_or_else(|| options.output_directory.clone()),
+        }
+    }
+
+    pub fn backend(mut self, backend: ArduinoBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn executable(mut self, executable: PathBuf) -> Self {
+        self.executable = Some(executable);
+        self
+    }
+
+    pub fn sketch_directory(mut self, sketch_directory: PathBuf) -> Self {
+        self.sketch_directory = sketch_directory;
+        self
     }
+
     pub fn verify(&self) -> Result<(), Error> {
         self.run(ArduinoCommand::Verify)
     }
@@ -42,25 +84,43 @@ impl ArduinoIDE {
         })
     }
 
-    /// The name/location of the arduino IDE
-    fn executable() -> &'static str {
-        "arduino"
+    /// The name/location of the arduino IDE or arduino-cli
+    fn executable_path(&self) -> PathBuf {
+        match self.executable {
+            Some(ref path) => path.to_owned(),
+            None => match self.backend {
+                ArduinoBackend::ArduinoIde => "arduino".into(),
+                ArduinoBackend::ArduinoCli => "arduino-cli".into(),
+            },
+        }
+    }
+
+    /// The path to the .ino file. The Arduino IDE requires the file name to
+    /// match the name of the directory it is in.
+    fn sketch_file(&self) -> PathBuf {
+        let name = self
+            .sketch_directory
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.sketch_directory.join(format!("{}.ino", name))
     }
 
-    /// The path to the .ino file, relative to the crate root. The Arduino IDE
-    /// requires the file name to match the name of the directory it is in.
-    fn sketch_file() -> &'static str {
-        "pipit-firmware/pipit-firmware.ino"
+    fn run(&self, command: ArduinoCommand) -> Result<(), Error> {
+        match self.backend {
+            ArduinoBackend::ArduinoIde => self.run_ide(command),
+            ArduinoBackend::ArduinoCli => self.run_cli(command),
+        }
     }
 
     /// Open the Arduino IDE non-interactively in a new process, and tell it to
     /// run the given comand.
-    fn run(&self, command: ArduinoCommand) -> Result<(), Error> {
+    fn run_ide(&self, command: ArduinoCommand) -> Result<(), Error> {
         println!("\nOpening the Arduino IDE.");
-        let status = Command::new(Self::executable())
-            .args(command.args())
-            .args(&["--board", self.board.ide_name()])
-            .arg(Self::sketch_file())
+        let status = Command::new(self.executable_path())
+            .args(command.ide_args())
+            .args(["--board", self.board.info().ide_name])
+            .arg(self.sketch_file())
             .status()
             .context("Failed to open Arduino IDE")?;
 
@@ -75,17 +135,35 @@ impl ArduinoIDE {
         };
         Err(Error::Arduino.context(context))
     }
-}
 
-impl BoardName {
-    fn ide_name(self) -> &'static str {
-        match self {
-            BoardName::TeensyLC => unimplemented!(),
+    /// Run arduino-cli and capture its output, so that it can be shown along
+    /// with the error if the build fails.
+    fn run_cli(&self, command: ArduinoCommand) -> Result<(), Error> {
+        println!("\nRunning arduino-cli.");
+        let output = Command::new(self.executable_path())
+            .args(command.cli_args())
+            .args(["--fqbn", self.board.info().ide_name])
+            .arg(&self.sketch_directory)
+            .output()
+            .context("Failed to run arduino-cli")?;
+
+        let build_output = format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        if output.status.success() {
+            print!("{}", build_output);
+            return Ok(());
         }
+        let status = match output.status.code() {
+            Some(code) => format!("exit status {}", code),
+            None => "termination by signal".to_owned(),
+        };
+        Err(Error::Arduino.context(&format!(
+            "Build failed or upload failed ({}), with output:\n{}",
+            status,
+            build_output.trim_end()
+        )))
     }
-    // fn parameters(&self) -> Option<&'static str> {
-    //     match self {
-    //         BoardName::TeensyLC => unimplemented!(),
-    //     }
-    // }
 }
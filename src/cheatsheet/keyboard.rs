@@ -2,7 +2,7 @@ use svg::node::element::Group;
 use svg::Node;
 
 use cheatsheet::switch::{Content, Switch, SwitchStyle, Symbol};
-use error::Error;
+use error::{Error, ResultExt};
 use types::{ModeName, Name, TutorData};
 
 use cheatsheet::draw::{Color, P2, V2};
@@ -35,19 +35,18 @@ impl Keyboard {
         mode: &ModeName,
     ) -> Result<(), Error> {
         assert_eq!(data.chord_spec.num_switches, self.switches.len());
-        let chords = chord_names
-            .iter()
-            .map(|name| data.chord(name, mode))
-            .collect::<Result<Vec<_>, _>>()?;
-
-        let symbols = chord_names
-            .iter()
-            .map(Symbol::from_name)
-            .collect::<Result<Vec<_>, _>>()?;
-
         let mut chord_style_iter = SwitchStyle::chord_style_iter();
 
-        for (chord, symbol) in chords.into_iter().zip(symbols.into_iter()) {
+        for name in chord_names {
+            let describe = || match data.span(name, mode) {
+                Some(span) => {
+                    format!("Failed to draw '{}' from {}", name, span)
+                }
+                None => format!("Failed to draw '{}'", name),
+            };
+            let chord = data.chord(name, mode).with_context(describe)?;
+            let symbol = Symbol::from_name(name).with_context(describe)?;
+
             let style = if chord.count_pressed() == 1 {
                 SwitchStyle::Single
             } else {
@@ -55,7 +54,10 @@ impl Keyboard {
                 // We should also consume a chord style if 0 switches are
                 // pressed, meaning this is a blank chord named "", used for
                 // skipping colors.
-                chord_style_iter.next().ok_or(Error::Style)?
+                chord_style_iter
+                    .next()
+                    .ok_or(Error::Style)
+                    .with_context(describe)?
             };
 
             let content = Content {
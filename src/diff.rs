@@ -0,0 +1,338 @@
+//! Compare the chords, words, and macros of two versions of the settings, since
+//! changes to kmap files are hard to read in a normal diff.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+use error::Error;
+use types::{
+    AllData, Chord, KmapOrder, ModeName, Name, SeqType, Sequence, Word,
+};
+
+#[derive(Debug, Default)]
+pub struct SettingsDiff {
+    pub added_modes: Vec<ModeName>,
+    pub removed_modes: Vec<ModeName>,
+    /// Only modes that exist in both versions, and have changed.
+    pub modes: Vec<ModeDiff>,
+    pub words: ListDiff,
+    pub macros: ListDiff,
+    pub huffman: HuffmanDiff,
+}
+
+#[derive(Debug)]
+pub struct ModeDiff {
+    pub mode: ModeName,
+    pub added: Vec<Name>,
+    pub removed: Vec<Name>,
+    /// Names that now have a different chord.
+    pub changed: Vec<Name>,
+    /// Chords that now belong to different names, as (old names, new names).
+    pub reassigned: Vec<(Vec<Name>, Vec<Name>)>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ListDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+/// Sizes that affect how much memory the sequences take up in the firmware.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct HuffmanDiff {
+    pub num_keys: (usize, usize),
+    pub max_code_length: (usize, usize),
+    /// The total length of all the encoded sequences.
+    pub sequence_bits: (usize, usize),
+}
+
+type ModeChords<'a> = BTreeMap<&'a Name, &'a Chord<KmapOrder>>;
+
+impl SettingsDiff {
+    pub fn new(old: &AllData, new: &AllData) -> Result<Self, Error> {
+        let mut diff = SettingsDiff::default();
+        for mode in old.modes.keys() {
+            if !new.modes.contains_key(mode) {
+                diff.removed_modes.push(mode.to_owned());
+            }
+        }
+        for mode in new.modes.keys() {
+            if !old.modes.contains_key(mode) {
+                diff.added_modes.push(mode.to_owned());
+                continue;
+            }
+            let mode_diff = ModeDiff::new(
+                mode,
+                &mode_chords(old, mode)?,
+                &mode_chords(new, mode)?,
+            );
+            if !mode_diff.is_empty() {
+                diff.modes.push(mode_diff);
+            }
+        }
+
+        diff.words = ListDiff::new(&words(old), &words(new));
+        diff.macros = ListDiff::new(&macros(old)?, &macros(new)?);
+        diff.huffman = HuffmanDiff {
+            num_keys: (
+                old.encoder.huffman_table.0.len(),
+                new.encoder.huffman_table.0.len(),
+            ),
+            max_code_length: (
+                old.encoder.huffman_table.max_length(),
+                new.encoder.huffman_table.max_length(),
+            ),
+            sequence_bits: (sequence_bits(old)?, sequence_bits(new)?),
+        };
+        Ok(diff)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added_modes.is_empty()
+            && self.removed_modes.is_empty()
+            && self.modes.is_empty()
+            && self.words.is_empty()
+            && self.macros.is_empty()
+            && self.huffman.is_empty()
+    }
+}
+
+impl ModeDiff {
+    fn new(mode: &ModeName, old: &ModeChords, new: &ModeChords) -> Self {
+        let mut diff = ModeDiff {
+            mode: mode.to_owned(),
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+            reassigned: Vec::new(),
+        };
+        for (&name, &chord) in old {
+            match new.get(name) {
+                None => diff.removed.push(name.to_owned()),
+                Some(&new_chord) if new_chord != chord => {
+                    diff.changed.push(name.to_owned())
+                }
+                Some(_) => (),
+            }
+        }
+        for &name in new.keys() {
+            if !old.contains_key(name) {
+                diff.added.push(name.to_owned());
+            }
+        }
+
+        let old_owners = chord_owners(old);
+        let new_owners = chord_owners(new);
+        let mut reassigned: Vec<_> = old_owners
+            .iter()
+            .filter_map(|(chord, old_names)| {
+                let new_names = new_owners.get(chord)?;
+                if new_names == old_names {
+                    return None;
+                }
+                let names = |names: &BTreeSet<&Name>| {
+                    names.iter().map(|&name| name.to_owned()).collect()
+                };
+                Some((names(old_names), names(new_names)))
+            })
+            .collect();
+        reassigned.sort();
+        diff.reassigned = reassigned;
+        diff
+    }
+
+    fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+            && self.reassigned.is_empty()
+    }
+}
+
+impl ListDiff {
+    fn new<T>(old: &BTreeMap<String, T>, new: &BTreeMap<String, T>) -> Self
+    where
+        T: PartialEq,
+    {
+        let mut diff = ListDiff::default();
+        for (key, value) in old {
+            match new.get(key) {
+                None => diff.removed.push(key.to_owned()),
+                Some(new_value) if new_value != value => {
+                    diff.changed.push(key.to_owned())
+                }
+                Some(_) => (),
+            }
+        }
+        for key in new.keys() {
+            if !old.contains_key(key) {
+                diff.added.push(key.to_owned());
+            }
+        }
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+    }
+}
+
+impl HuffmanDiff {
+    fn is_empty(&self) -> bool {
+        self.num_keys.0 == self.num_keys.1
+            && self.max_code_length.0 == self.max_code_length.1
+            && self.sequence_bits.0 == self.sequence_bits.1
+    }
+}
+
+/// The chord for each name in the mode, taking the first one found in the
+/// mode's kmaps like the firmware does.
+fn mode_chords<'a>(
+    data: &'a AllData,
+    mode: &ModeName,
+) -> Result<ModeChords<'a>, Error> {
+    let mut chords = ModeChords::new();
+    for kmap in data.modes[mode].kmap_paths() {
+        for (name, chord) in data.chords.get_kmap(kmap)?.iter() {
+            chords.entry(name).or_insert(chord);
+        }
+    }
+    Ok(chords)
+}
+
+fn chord_owners<'a>(
+    chords: &ModeChords<'a>,
+) -> HashMap<&'a Chord<KmapOrder>, BTreeSet<&'a Name>> {
+    let mut owners: HashMap<_, BTreeSet<_>> = HashMap::new();
+    for (&name, &chord) in chords {
+        owners.entry(chord).or_default().insert(name);
+    }
+    owners
+}
+
+/// Each dictionary word, along with its anagram number and alternate chord.
+fn words(data: &AllData) -> BTreeMap<String, &Word> {
+    data.dictionary
+        .iter()
+        .map(|word| (word.word.to_owned(), word))
+        .collect()
+}
+
+fn macros(data: &AllData) -> Result<BTreeMap<String, &Sequence>, Error> {
+    let seq_map = data.sequences.get_seq_map(SeqType::Macro)?;
+    seq_map
+        .names()
+        .map(|name| {
+            let seq = data.sequences.get(name, SeqType::Macro)?;
+            Ok((name.to_string(), seq))
+        })
+        .collect()
+}
+
+fn sequence_bits(data: &AllData) -> Result<usize, Error> {
+    let mut total = 0;
+    for &seq_type in data.sequences.seq_types() {
+        let seq_map = data.sequences.get_seq_map(seq_type)?;
+        for name in seq_map.names() {
+            let seq = data.sequences.get(name, seq_type)?;
+            total += seq.formatted_length_in_bits(&data.encoder)?;
+        }
+    }
+    Ok(total)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+impl fmt::Display for SettingsDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences found.");
+        }
+        write_list(f, "Added modes", &self.added_modes)?;
+        write_list(f, "Removed modes", &self.removed_modes)?;
+        for mode_diff in &self.modes {
+            write!(f, "{}", mode_diff)?;
+        }
+        if !self.words.is_empty() {
+            writeln!(f, "Dictionary:")?;
+            write!(f, "{}", self.words)?;
+        }
+        if !self.macros.is_empty() {
+            writeln!(f, "Macros:")?;
+            write!(f, "{}", self.macros)?;
+        }
+        if !self.huffman.is_empty() {
+            writeln!(f, "Huffman table:")?;
+            write!(f, "{}", self.huffman)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ModeDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Mode {}:", self.mode)?;
+        write_list(f, "  Added chords", &self.added)?;
+        write_list(f, "  Removed chords", &self.removed)?;
+        write_list(f, "  Changed chords", &self.changed)?;
+        if !self.reassigned.is_empty() {
+            writeln!(f, "  Reassigned chords:")?;
+            for (old_names, new_names) in &self.reassigned {
+                writeln!(f, "    {} -> {}", join(old_names), join(new_names))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ListDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_list(f, "  Added", &self.added)?;
+        write_list(f, "  Removed", &self.removed)?;
+        write_list(f, "  Changed", &self.changed)
+    }
+}
+
+impl fmt::Display for HuffmanDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows = [
+            ("Keys", self.num_keys),
+            ("Longest code (bits)", self.max_code_length),
+            ("Encoded sequences (bits)", self.sequence_bits),
+        ];
+        for (label, (old, new)) in &rows {
+            if old != new {
+                writeln!(f, "  {}: {} -> {}", label, old, new)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_list<T>(
+    f: &mut fmt::Formatter,
+    label: &str,
+    items: &[T],
+) -> fmt::Result
+where
+    T: fmt::Display,
+{
+    if items.is_empty() {
+        return Ok(());
+    }
+    writeln!(f, "{}: {}", label, join(items))
+}
+
+fn join<T>(items: &[T]) -> String
+where
+    T: fmt::Display,
+{
+    items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
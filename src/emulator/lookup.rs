@@ -0,0 +1,78 @@
+use bit_vec::BitVec;
+use std::collections::{BTreeMap, HashMap};
+
+use error::{Error, ResultExt};
+use types::{
+    AllData, Chord, KmapOrder, KmapPath, ModeInfo, SeqType, Sequence,
+    SequenceEncoder,
+};
+
+type SeqTypeLookup = HashMap<Chord<KmapOrder>, BitVec<u8>>;
+
+/// The chords and huffman-encoded sequences for every kmap, like the lookup
+/// structs in the generated firmware config. The sequences are encoded ahead
+/// of time and only decoded once they're found, the same way the firmware
+/// does it.
+pub struct Lookups<'a> {
+    kmaps: BTreeMap<&'a KmapPath, BTreeMap<SeqType, SeqTypeLookup>>,
+    encoder: &'a SequenceEncoder,
+}
+
+impl<'a> Lookups<'a> {
+    pub fn new(data: &'a AllData) -> Result<Self, Error> {
+        let mut kmaps = BTreeMap::new();
+        for (kmap, chord_map) in data.chords.iter() {
+            let mut seq_type_lookups = BTreeMap::new();
+            for &seq_type in data.sequences.seq_types() {
+                let mut entries = Vec::new();
+                for name in data.sequences.get_seq_map(seq_type)?.names() {
+                    if let Some(chord) = chord_map.get(name) {
+                        let bits = data
+                            .sequences
+                            .get(name, seq_type)?
+                            .as_bits(&data.encoder)
+                            .with_context(|| {
+                                format!("Failed to encode sequence '{}'", name)
+                            })?;
+                        entries.push((chord, bits));
+                    }
+                }
+                // The firmware searches shorter sequences first, so they win
+                // if several sequences share a chord.
+                entries.sort_by_key(|(_, bits)| bits.len());
+
+                let mut lookup = HashMap::new();
+                for (chord, bits) in entries {
+                    lookup.entry(chord.to_owned()).or_insert(bits);
+                }
+                seq_type_lookups.insert(seq_type, lookup);
+            }
+            kmaps.insert(kmap, seq_type_lookups);
+        }
+        Ok(Self {
+            kmaps,
+            encoder: &data.encoder,
+        })
+    }
+
+    /// Search the mode's kmaps in order, and decode the sequence for the first
+    /// exact match of the chord (including its anagram number).
+    pub fn find(
+        &self,
+        chord: &Chord<KmapOrder>,
+        seq_type: SeqType,
+        mode_info: &ModeInfo,
+    ) -> Result<Option<Sequence>, Error> {
+        for kmap in mode_info.kmap_paths() {
+            let bits = self
+                .kmaps
+                .get(kmap)
+                .and_then(|lookups| lookups.get(&seq_type))
+                .and_then(|lookup| lookup.get(chord));
+            if let Some(bits) = bits {
+                return Ok(Some(self.encoder.decode(bits)?));
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,611 @@
+//! This module emulates the firmware on the host computer. It replays a stream
+//! of timestamped switch presses and releases against the same lookup tables
+//! and huffman encodings that would be saved in the firmware config, and
+//! records what the keyboard would have sent to the host.
+//!
+//! It follows the firmware's `Scanner`, `Pipit`, and `Sender` classes, but
+//! only models the parts that affect what gets typed. Commands other than
+//! pausing and switching modes are recorded, but not performed.
+
+mod lookup;
+mod scanner;
+
+pub use self::scanner::SwitchEvent;
+
+use serde_yaml;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::PathBuf;
+
+use self::lookup::Lookups;
+use self::scanner::{Packet, Scanner};
+use error::{Error, ResultExt};
+use types::{
+    AllData, AnagramNum, CCode, CEnumVariant, Chord, KeyDefs, KeyPress,
+    KmapOrder, ModeInfo, ModeName, Name, SeqType, Sequence, ToC,
+    WordSpacePosition,
+};
+use util::read_file;
+
+// The firmware hardcodes these names, too.
+const MOD_CAPITAL: &str = "mod_capital";
+const MOD_NOSPACE: &str = "mod_nospace";
+const MOD_DOUBLE: &str = "mod_double";
+const MOD_SHORTEN: &str = "mod_shorten";
+const COMMAND_PAUSE: &str = "command_pause";
+const COMMAND_SWITCH_TO: &str = "command_switch_to";
+
+/// Something the emulated keyboard did in response to a chord.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Output {
+    /// A report containing keys and/or modifiers was sent to the host.
+    Press(Report),
+    /// A command was looked up. Only pausing and switching modes are
+    /// actually performed.
+    Command(Name),
+    /// The chord wasn't found in any lookup.
+    Unknown(Chord<KmapOrder>),
+}
+
+/// The keys and modifiers sent to the host in a single USB report. Only
+/// gaming modes send multiple keys at once.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Report {
+    pub keys: Vec<CCode>,
+    pub mods: Vec<CCode>,
+}
+
+pub struct Emulator<'a> {
+    data: &'a AllData,
+    lookups: Lookups<'a>,
+    scanner: Scanner,
+    mode: ModeName,
+    is_paused: bool,
+    /// The last letter sent, for use by `mod_double`.
+    last_letter: Option<KeyPress>,
+    outputs: Vec<Output>,
+}
+
+/// A chord being processed, along with the flags for any modifiers that have
+/// been extracted from it. Like the firmware's `Chord` class.
+struct PressedChord {
+    chord: Chord<KmapOrder>,
+    mods: BTreeSet<Name>,
+}
+
+#[derive(Clone, Copy)]
+enum ModType {
+    Plain,
+    Word,
+    Anagram,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Load a list of switch events from a yaml file.
+pub fn load_events(path: &PathBuf) -> Result<Vec<SwitchEvent>, Error> {
+    Ok(serde_yaml::from_str(&read_file(path)?)?)
+}
+
+/// Approximate the text that the outputs would type into a text editor.
+/// Backspaces delete the previous character, and keys without a spelling
+/// are shown in angle brackets, like `<KEY_F1>`.
+pub fn render_text(outputs: &[Output]) -> String {
+    let backspace = "KEY_BACKSPACE".to_c();
+    let mut text = String::new();
+    for output in outputs {
+        if let Output::Press(report) = output {
+            for key in &report.keys {
+                if *key == backspace {
+                    text.pop();
+                } else {
+                    text.push_str(&spell(key, &report.mods));
+                }
+            }
+        }
+    }
+    text
+}
+
+fn spell(key: &CCode, mods: &[CCode]) -> String {
+    let keypress = KeyPress {
+        key: Some(key.to_owned()),
+        mods: mods.to_owned(),
+    };
+    if let Ok(Some(spelling)) = KeyDefs::spelling_from_keypress(&keypress) {
+        return spelling.to_string();
+    }
+    // Letters aren't listed with shift in the key definitions, so try
+    // capitalizing them ourselves.
+    if mods == ["MODIFIERKEY_SHIFT".to_c()] {
+        let lower = KeyPress::new_key(key);
+        if let Ok(Some(spelling)) = KeyDefs::spelling_from_keypress(&lower) {
+            return spelling.0.to_uppercase().collect();
+        }
+    }
+    format!("<{}>", Report::from(keypress))
+}
+
+impl<'a> Emulator<'a> {
+    pub fn new(data: &'a AllData) -> Result<Self, Error> {
+        // The firmware starts in the mode with index 0.
+        let mode = data
+            .modes
+            .keys()
+            .next()
+            .ok_or_else(|| Error::Empty("modes".into()))?
+            .to_owned();
+        let scanner = Scanner::new(
+            &data.chord_spec,
+            data.user_options.chord_delay.0.into(),
+            data.user_options.held_delay.0.into(),
+            data.modes[&mode].gaming,
+        );
+        Ok(Self {
+            data,
+            lookups: Lookups::new(data)
+                .context("Failed to make emulator lookups")?,
+            scanner,
+            mode,
+            is_paused: false,
+            last_letter: None,
+            outputs: Vec::new(),
+        })
+    }
+
+    /// Replay the switch events and return everything the keyboard did in
+    /// response. Events don't need to be sorted, but events with the same
+    /// time will be applied in the order given.
+    pub fn run(
+        &mut self,
+        mut events: Vec<SwitchEvent>,
+    ) -> Result<Vec<Output>, Error> {
+        events.sort_by_key(|event| event.time);
+        let mut events = events.into_iter().peekable();
+        loop {
+            let next_event_time = events.peek().map(|event| event.time);
+            let now = match (next_event_time, self.scanner.next_deadline()) {
+                (Some(a), Some(b)) => a.min(b),
+                (Some(a), None) | (None, Some(a)) => a,
+                (None, None) => break,
+            };
+            while events.peek().is_some_and(|event| event.time == now) {
+                let event = events.next().expect("failed to get next event");
+                self.scanner.read(&event).with_context(|| {
+                    format!("Invalid switch event at time {}", event.time)
+                })?;
+            }
+            self.scanner.update_switches(now);
+            if let Some(packet) = self.scanner.detect_chords(now) {
+                self.process(packet)?;
+            }
+        }
+        Ok(self.outputs.split_off(0))
+    }
+
+    fn process(&mut self, packet: Packet) -> Result<(), Error> {
+        match packet {
+            Packet::Press(chord) => {
+                if self.mode_info().gaming {
+                    self.process_gaming_switches(&chord)
+                } else {
+                    self.process_press(chord)
+                }
+            }
+            Packet::Release => Ok(()),
+        }
+    }
+
+    fn process_press(&mut self, chord: Chord<KmapOrder>) -> Result<(), Error> {
+        let mut chord = PressedChord::new(chord);
+
+        if self.do_if_found(SeqType::Command, &mut chord)? {
+            return Ok(());
+        }
+        if self.is_paused {
+            return Ok(());
+        }
+        if self.do_if_found(SeqType::Macro, &mut chord)? {
+            return Ok(());
+        }
+
+        for name in &self.data.word_mods {
+            self.extract_mod(&mut chord, name, ModType::Word);
+        }
+        self.extract_anagram_mods(&mut chord);
+        if self.do_if_found(SeqType::Word, &mut chord)? {
+            return Ok(());
+        }
+
+        self.restore_mods(&mut chord, &self.data.anagram_mods)?;
+        chord.chord.anagram_num = AnagramNum::default();
+        self.restore_mods(&mut chord, &self.data.word_mods)?;
+        for name in &self.data.plain_mods {
+            self.extract_mod(&mut chord, name, ModType::Plain);
+        }
+        if self.do_if_found(SeqType::Plain, &mut chord)? {
+            return Ok(());
+        }
+
+        if chord.chord.is_empty() {
+            // Only modifiers were pressed, so send them now.
+            let mods = self.mod_byte(&chord)?;
+            if !mods.is_empty() {
+                self.send(Report {
+                    keys: Vec::new(),
+                    mods,
+                });
+            }
+            self.reuse_mods(&chord);
+            return Ok(());
+        }
+
+        self.restore_mods(&mut chord, &self.data.plain_mods)?;
+        self.outputs.push(Output::Unknown(chord.chord));
+        Ok(())
+    }
+
+    /// Look up each pressed switch on its own, like a normal keyboard.
+    /// Commands and macros are done immediately, and all the plain keys are
+    /// sent together in a single report.
+    fn process_gaming_switches(
+        &mut self,
+        chord: &Chord<KmapOrder>,
+    ) -> Result<(), Error> {
+        let mut report = Report::default();
+        let switches: Vec<bool> = chord.iter().collect();
+        for (i, &is_pressed) in switches.iter().enumerate() {
+            if !is_pressed {
+                continue;
+            }
+            let mut single = self.data.chord_spec.new_chord();
+            single.set_switch(i, true);
+            let mut single = PressedChord::new(single);
+
+            if self.do_if_found(SeqType::Command, &mut single)? {
+                return Ok(());
+            }
+            if self.is_paused {
+                return Ok(());
+            }
+            if self.do_if_found(SeqType::Macro, &mut single)? {
+                return Ok(());
+            }
+            match self.lookup(&single, SeqType::Plain)? {
+                Some(seq) => {
+                    let keypress = seq.lone_keypress()?;
+                    report.keys.extend(keypress.key);
+                    add_mods(&mut report.mods, &keypress.mods);
+                }
+                None => self.outputs.push(Output::Unknown(single.chord)),
+            }
+        }
+        if !report.is_empty() {
+            self.send(report);
+        }
+        Ok(())
+    }
+
+    /// If the chord is found in a lookup of the given type, send or do it and
+    /// return true. Otherwise return false.
+    fn do_if_found(
+        &mut self,
+        seq_type: SeqType,
+        chord: &mut PressedChord,
+    ) -> Result<bool, Error> {
+        let seq = match self.lookup(chord, seq_type)? {
+            Some(seq) => seq,
+            None => return Ok(false),
+        };
+        self.reuse_mods(chord);
+        match seq_type {
+            SeqType::Plain => self.send_plain(&seq, chord)?,
+            SeqType::Word => self.send_word(&seq, chord),
+            SeqType::Macro => self.send_macro(&seq),
+            SeqType::Command => self.do_command(&seq)?,
+        }
+        Ok(true)
+    }
+
+    fn lookup(
+        &self,
+        chord: &PressedChord,
+        seq_type: SeqType,
+    ) -> Result<Option<Sequence>, Error> {
+        self.lookups
+            .find(&chord.chord, seq_type, self.mode_info())
+            .with_context(|| format!("Failed to look up {} chord", seq_type))
+    }
+
+    fn do_command(&mut self, seq: &Sequence) -> Result<(), Error> {
+        let mut args = seq.keypresses().map(|keypress| keypress.key_or_blank());
+        let variant = args
+            .next()
+            .ok_or_else(|| Error::Empty("command sequence".into()))?;
+        let command = self
+            .data
+            .commands
+            .iter()
+            .find(|command| command.qualified_enum_variant() == variant)
+            .ok_or_else(|| Error::LookupErr {
+                key: variant.to_string(),
+                container: "commands".into(),
+            })?;
+        let mut name = command.name().to_owned();
+
+        // Pausing is the only command allowed while paused.
+        if name == Name::from(COMMAND_PAUSE) {
+            self.is_paused = !self.is_paused;
+        } else if self.is_paused {
+            return Ok(());
+        } else if name == Name::from(COMMAND_SWITCH_TO) {
+            let arg = args.next().ok_or_else(|| {
+                Error::Empty("argument for mode-switching command".into())
+            })?;
+            let mode = self
+                .data
+                .modes
+                .keys()
+                .find(|mode| mode.qualified_enum_variant() == arg)
+                .ok_or_else(|| Error::LookupErr {
+                    key: arg.to_string(),
+                    container: "modes".into(),
+                })?
+                .to_owned();
+            name = Name::from(format!("{}_{}", name, mode));
+            self.mode = mode;
+            self.scanner.set_gaming(self.mode_info().gaming);
+        }
+        self.outputs.push(Output::Command(name));
+        Ok(())
+    }
+
+    fn send_plain(
+        &mut self,
+        seq: &Sequence,
+        chord: &PressedChord,
+    ) -> Result<(), Error> {
+        let mods = self.mod_byte(chord)?;
+        for keypress in seq.keypresses() {
+            let mut report = Report::from(keypress.to_owned());
+            add_mods(&mut report.mods, &mods);
+            if chord.has_mod(MOD_CAPITAL) {
+                report.add_shift();
+            }
+            self.send(report);
+        }
+        Ok(())
+    }
+
+    fn send_word(&mut self, seq: &Sequence, chord: &mut PressedChord) {
+        let space_position = self.data.user_options.word_space_position;
+        let is_space_before =
+            matches!(space_position, WordSpacePosition::Before);
+        let is_space_after = matches!(space_position, WordSpacePosition::After);
+
+        // Doubling and shortening would be pretty useless with a space before.
+        if is_space_before
+            && (chord.has_mod(MOD_SHORTEN) || chord.has_mod(MOD_DOUBLE))
+        {
+            chord.mods.insert(Name::from(MOD_NOSPACE));
+        }
+        if chord.has_mod(MOD_SHORTEN) {
+            self.send_key("KEY_BACKSPACE");
+        }
+        if chord.has_mod(MOD_DOUBLE) {
+            if let Some(letter) = self.last_letter.clone() {
+                self.send(Report::from(letter));
+            }
+        }
+        if is_space_before && !chord.has_mod(MOD_NOSPACE) {
+            self.send_key("KEY_SPACE");
+        }
+
+        for (i, keypress) in seq.keypresses().enumerate() {
+            let mut report = Report::from(keypress.to_owned());
+            if i == 0 && chord.has_mod(MOD_CAPITAL) {
+                report.add_shift();
+            }
+            self.send(report);
+        }
+
+        if is_space_after && !chord.has_mod(MOD_NOSPACE) {
+            self.send_key("KEY_SPACE");
+        }
+    }
+
+    fn send_macro(&mut self, seq: &Sequence) {
+        for keypress in seq.keypresses() {
+            self.send(Report::from(keypress.to_owned()));
+        }
+    }
+
+    fn send_key(&mut self, key: &str) {
+        self.send(Report::from(KeyPress::new_key(key)));
+    }
+
+    fn send(&mut self, report: Report) {
+        let is_letter = report.keys.iter().any(|key| {
+            !["KEY_BACKSPACE", "KEY_LEFT", "KEY_RIGHT"]
+                .contains(&key.0.as_str())
+        });
+        if is_letter {
+            self.last_letter = Some(KeyPress {
+                key: report.keys.last().cloned(),
+                mods: report.mods.clone(),
+            });
+        }
+        self.outputs.push(Output::Press(report));
+    }
+
+    /// Hold any extracted modifiers, so they'll be included in the next chord
+    /// if their switches are still pressed.
+    fn reuse_mods(&mut self, chord: &PressedChord) {
+        let mut mods = self.data.chord_spec.new_chord();
+        for name in &chord.mods {
+            if let Some(mod_chord) = self.mod_chord(name) {
+                mods.union_mut(&mod_chord)
+                    .expect("failed to union modifier chords");
+            }
+        }
+        if !mods.is_empty() {
+            self.scanner.hold_some(&mods);
+        }
+    }
+
+    /// The keyboard modifiers (like shift) of any plain modifiers that have
+    /// been extracted from the chord.
+    fn mod_byte(&self, chord: &PressedChord) -> Result<Vec<CCode>, Error> {
+        let mut mods = Vec::new();
+        for name in &self.data.plain_mods {
+            if chord.has_mod(&name.0) {
+                let keypress = self
+                    .data
+                    .sequences
+                    .get(name, SeqType::Plain)?
+                    .lone_keypress()?;
+                add_mods(&mut mods, &keypress.mods);
+            }
+        }
+        Ok(mods)
+    }
+
+    fn extract_anagram_mods(&self, chord: &mut PressedChord) {
+        chord.chord.anagram_num = AnagramNum::default();
+        let mask = self.data.get_anagram_mask(&self.mode);
+        if chord.chord.intersection(&mask).is_empty() {
+            return;
+        }
+        let numbers = self
+            .data
+            .get_anagram_mod_numbers()
+            .expect("failed to get anagram mod numbers");
+        for (name, num) in self.data.anagram_mods.iter().zip(numbers) {
+            if self.extract_mod(chord, name, ModType::Anagram) {
+                chord.chord.anagram_num = num;
+                return;
+            }
+        }
+    }
+
+    /// If the modifier is pressed in the chord, remove its switches from the
+    /// chord, set its flag, and return true.
+    fn extract_mod(
+        &self,
+        chord: &mut PressedChord,
+        name: &Name,
+        mod_type: ModType,
+    ) -> bool {
+        let mod_chord = match self.mod_chord(name) {
+            Some(mod_chord) => mod_chord,
+            // A missing mod chord is ignored.
+            None => return false,
+        };
+        let is_pressed = match mod_type {
+            ModType::Plain | ModType::Word => chord.chord.contains(&mod_chord),
+            ModType::Anagram => {
+                let mask = self.data.get_anagram_mask(&self.mode);
+                chord.chord.intersection(&mask).switches()
+                    == mod_chord.switches()
+            }
+        };
+        if is_pressed {
+            chord.mods.insert(name.to_owned());
+            chord.chord.subtract_mut(&mod_chord);
+        }
+        is_pressed
+    }
+
+    /// Put the switches of any extracted modifiers in the list back into the
+    /// chord, and unset their flags.
+    fn restore_mods(
+        &self,
+        chord: &mut PressedChord,
+        names: &[Name],
+    ) -> Result<(), Error> {
+        for name in names {
+            if chord.mods.remove(name) {
+                if let Some(mod_chord) = self.mod_chord(name) {
+                    chord.chord.union_mut(&mod_chord)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the chord for the modifier in the current mode, or None if it's
+    /// missing or blank.
+    fn mod_chord(&self, name: &Name) -> Option<Chord<KmapOrder>> {
+        self.data
+            .get_chord_in_mode(name, &self.mode)
+            .filter(|chord| !chord.is_empty())
+    }
+
+    fn mode_info(&self) -> &ModeInfo {
+        &self.data.modes[&self.mode]
+    }
+}
+
+impl PressedChord {
+    fn new(chord: Chord<KmapOrder>) -> Self {
+        Self {
+            chord,
+            mods: BTreeSet::new(),
+        }
+    }
+
+    fn has_mod(&self, name: &str) -> bool {
+        self.mods.contains(&Name::from(name))
+    }
+}
+
+impl Report {
+    fn is_empty(&self) -> bool {
+        self.keys.is_empty() && self.mods.is_empty()
+    }
+
+    fn add_shift(&mut self) {
+        add_mods(&mut self.mods, &["MODIFIERKEY_SHIFT".to_c()]);
+    }
+}
+
+fn add_mods(mods: &mut Vec<CCode>, new_mods: &[CCode]) {
+    for new_mod in new_mods {
+        if !mods.contains(new_mod) {
+            mods.push(new_mod.to_owned());
+        }
+    }
+}
+
+impl From<KeyPress> for Report {
+    fn from(keypress: KeyPress) -> Self {
+        Self {
+            keys: keypress.key.into_iter().collect(),
+            mods: keypress.mods,
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let codes: Vec<String> = self
+            .mods
+            .iter()
+            .chain(self.keys.iter())
+            .map(|code| code.to_string())
+            .collect();
+        write!(f, "{}", codes.join("+"))
+    }
+}
+
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Output::Press(report) => write!(f, "press:   {}", report),
+            Output::Command(name) => write!(f, "command: {}", name),
+            Output::Unknown(chord) => {
+                write!(f, "unknown: {:?}", chord.switches())
+            }
+        }
+    }
+}
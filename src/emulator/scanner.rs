@@ -0,0 +1,245 @@
+use error::Error;
+use types::{Chord, ChordSpec, KmapOrder};
+
+/// A single switch being pressed or released. Switches are numbered in kmap
+/// order, starting from zero. Times are in milliseconds.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SwitchEvent {
+    pub time: u32,
+    pub switch: usize,
+    pub pressed: bool,
+}
+
+/// What the scanner hands over to be looked up and sent, like the firmware's
+/// `Packet`.
+#[derive(Debug)]
+pub enum Packet {
+    Press(Chord<KmapOrder>),
+    /// The firmware distinguishes between partial and full releases, but they
+    /// both just release keys, which doesn't affect what gets typed.
+    Release,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SwitchStatus {
+    NotPressed,
+    Pressed,
+    AlreadySent,
+    Held,
+}
+
+/// Like the firmware's `Timer`, but driven by explicit timestamps instead of
+/// the system clock.
+#[derive(Debug, Default)]
+struct Timer {
+    default_value: u32,
+    deadline: Option<u32>,
+}
+
+/// A model of the firmware's `Scanner`, which groups switch presses into
+/// chords using the chord, release, and held timers.
+#[derive(Debug)]
+pub struct Scanner {
+    statuses: Vec<SwitchStatus>,
+    readings: Vec<bool>,
+    last_released_switch: Option<usize>,
+    chord_timer: Timer,
+    release_timer: Timer,
+    held_timer: Timer,
+    chord_delay: u32,
+    held_delay: u32,
+    is_gaming: bool,
+    blank_chord: Chord<KmapOrder>,
+}
+
+impl SwitchEvent {
+    /// Create events for pressing all the switches in the chord at the same
+    /// time, and then releasing them all after the given duration.
+    #[cfg(test)]
+    pub fn tap(
+        chord: &Chord<KmapOrder>,
+        time: u32,
+        duration: u32,
+    ) -> Vec<SwitchEvent> {
+        let switches: Vec<usize> = chord
+            .iter()
+            .enumerate()
+            .filter(|&(_, is_pressed)| is_pressed)
+            .map(|(switch, _)| switch)
+            .collect();
+        let presses = switches.iter().map(|&switch| SwitchEvent {
+            time,
+            switch,
+            pressed: true,
+        });
+        let releases = switches.iter().map(|&switch| SwitchEvent {
+            time: time + duration,
+            switch,
+            pressed: false,
+        });
+        presses.chain(releases).collect()
+    }
+}
+
+impl Timer {
+    fn start(&mut self, now: u32) {
+        self.deadline = Some(now + self.default_value);
+    }
+
+    /// Check if the timer is done, and disable it if it is.
+    fn is_done(&mut self, now: u32) -> bool {
+        match self.deadline {
+            Some(deadline) if deadline <= now => {
+                self.deadline = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Scanner {
+    pub fn new(
+        chord_spec: &ChordSpec,
+        chord_delay: u32,
+        held_delay: u32,
+        is_gaming: bool,
+    ) -> Self {
+        let mut scanner = Self {
+            statuses: vec![SwitchStatus::NotPressed; chord_spec.num_switches],
+            readings: vec![false; chord_spec.num_switches],
+            last_released_switch: None,
+            chord_timer: Timer::default(),
+            release_timer: Timer::default(),
+            held_timer: Timer::default(),
+            chord_delay,
+            held_delay,
+            is_gaming: false,
+            blank_chord: chord_spec.new_chord(),
+        };
+        scanner.set_gaming(is_gaming);
+        scanner
+    }
+
+    /// Gaming modes don't wait for chords, so all the timers expire
+    /// immediately.
+    pub fn set_gaming(&mut self, is_gaming: bool) {
+        self.is_gaming = is_gaming;
+        let (chord_delay, held_delay) = if is_gaming {
+            (0, 0)
+        } else {
+            (self.chord_delay, self.held_delay)
+        };
+        self.chord_timer.default_value = chord_delay;
+        self.release_timer.default_value = chord_delay;
+        self.held_timer.default_value = held_delay;
+    }
+
+    /// Record the new state of a switch. It won't take effect until the next
+    /// call to `update_switches()`.
+    pub fn read(&mut self, event: &SwitchEvent) -> Result<(), Error> {
+        if event.switch >= self.readings.len() {
+            return Err(Error::OutOfRangeErr {
+                name: "switch index".into(),
+                value: event.switch,
+                min: 0,
+                max: self.readings.len() - 1,
+            });
+        }
+        self.readings[event.switch] = event.pressed;
+        Ok(())
+    }
+
+    /// The earliest time at which a running timer will be done.
+    pub fn next_deadline(&self) -> Option<u32> {
+        [&self.chord_timer, &self.release_timer, &self.held_timer]
+            .iter()
+            .filter_map(|timer| timer.deadline)
+            .min()
+    }
+
+    pub fn update_switches(&mut self, now: u32) {
+        let mut was_switch_double_tapped = false;
+        for i in 0..self.statuses.len() {
+            let status = self.statuses[i];
+            if status == SwitchStatus::NotPressed && self.readings[i] {
+                self.chord_timer.start(now);
+                self.held_timer.start(now);
+                self.statuses[i] = SwitchStatus::Pressed;
+                was_switch_double_tapped |=
+                    self.last_released_switch == Some(i);
+                self.last_released_switch = None;
+            } else if status != SwitchStatus::NotPressed && !self.readings[i] {
+                self.release_timer.start(now);
+                self.held_timer.start(now);
+                self.statuses[i] = SwitchStatus::NotPressed;
+                self.last_released_switch = Some(i);
+            }
+        }
+        if was_switch_double_tapped {
+            self.already_sent_to_held();
+        }
+    }
+
+    pub fn detect_chords(&mut self, now: u32) -> Option<Packet> {
+        if self.held_timer.is_done(now) {
+            self.already_sent_to_held();
+        }
+
+        let is_chord_done = if self.is_gaming {
+            // Always check the release timer too, so it gets disabled.
+            let is_chord_done = self.chord_timer.is_done(now);
+            self.release_timer.is_done(now) || is_chord_done
+        } else {
+            self.chord_timer.is_done(now)
+        };
+
+        if is_chord_done {
+            let chord = self.make_chord();
+            if chord.is_empty() {
+                // Like the firmware, treat an empty chord as a full release.
+                Some(Packet::Release)
+            } else {
+                Some(Packet::Press(chord))
+            }
+        } else if self.release_timer.is_done(now) {
+            Some(Packet::Release)
+        } else {
+            None
+        }
+    }
+
+    /// Change the status of any switches in the given chord that are still
+    /// down to `Held`, so they'll be sent again as part of the next chord.
+    pub fn hold_some(&mut self, switches: &Chord<KmapOrder>) {
+        for (status, is_held) in self.statuses.iter_mut().zip(switches.iter()) {
+            if is_held && *status != SwitchStatus::NotPressed {
+                *status = SwitchStatus::Held;
+            }
+        }
+    }
+
+    fn make_chord(&mut self) -> Chord<KmapOrder> {
+        let mut chord = self.blank_chord.clone();
+        for (i, status) in self.statuses.iter_mut().enumerate() {
+            match *status {
+                SwitchStatus::Pressed => {
+                    chord.set_switch(i, true);
+                    *status = SwitchStatus::AlreadySent;
+                }
+                SwitchStatus::Held => chord.set_switch(i, true),
+                _ => (),
+            }
+        }
+        chord
+    }
+
+    fn already_sent_to_held(&mut self) {
+        for status in &mut self.statuses {
+            if *status == SwitchStatus::AlreadySent {
+                *status = SwitchStatus::Held;
+            }
+        }
+    }
+}
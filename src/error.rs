@@ -1,3 +1,4 @@
+use serde_json;
 use serde_yaml;
 use std::error;
 use std::fmt;
@@ -28,6 +29,14 @@ pub enum Error {
         value: String,
     },
     Empty(String),
+    DecodeMismatch(Vec<String>),
+    CheckFailed {
+        errors: usize,
+    },
+    Unsupported {
+        feature: String,
+        board: String,
+    },
     PermuteLength,
     PermuteWouldDrop,
     Arduino,
@@ -37,6 +46,7 @@ pub enum Error {
     Style,
     Io(io::Error),
     SerdeYaml(serde_yaml::Error),
+    SerdeJson(serde_json::Error),
     Context {
         message: String,
         cause: Box<Error>,
@@ -83,13 +93,25 @@ impl fmt::Display for Error {
             Error::Empty (thing) => {
                 write!(f, "{} is empty", thing)
             }
+            Error::DecodeMismatch(differences) => write!(
+                f,
+                "Decoded firmware config doesn't match the settings:\n  {}",
+                differences.join("\n  ")
+            ),
+            Error::CheckFailed { errors } => {
+                write!(f, "Check failed with {} error(s)", errors)
+            }
+            Error::Unsupported { feature, board } => {
+                write!(f, "{} is not supported on board: '{}'", feature, board)
+            }
             Error::PermuteLength => write!(f, "Unable to permute: input sequence is the wrong length"),
             Error::PermuteWouldDrop => write!(f, "Unable to create permutation: an element in the old sequence is not present in the new sequence"),
             Error::KmapSyntaxErr{line} => write!(f, "Syntax error in kmap file near line {}.", line),
             Error::Style => write!(f, "Ran out of unique switch fill styles"),
-            Error::Arduino => write!(f, "Error in Arduino IDE"),
+            Error::Arduino => write!(f, "Error in Arduino IDE or arduino-cli"),
             Error::Io(err) => write!(f, "Input/output error: {}", err),
             Error::SerdeYaml(err) => write!(f, "Yaml file error: {}", err),
+            Error::SerdeJson(err) => write!(f, "Json error: {}", err),
             Error::Context { message, cause } => {
                 write!(f, "{}\n  caused by: {}", message, cause)
             }
@@ -109,6 +131,12 @@ impl From<serde_yaml::Error> for Error {
     }
 }
 
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Error {
+        Error::SerdeJson(err)
+    }
+}
+
 impl error::Error for Error {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self {
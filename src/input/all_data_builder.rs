@@ -1,24 +1,29 @@
 use std::collections::{BTreeMap, BTreeSet};
 use std::path::PathBuf;
 
-use serde_yaml;
-
 use input::Settings;
 
 use error::{Error, ResultExt};
 use types::{
     AllChordMaps, AllData, AllSeqMaps, CCode, CEnumVariant, Chord, Command,
-    HuffmanTable, KeyDefs, KeyPress, KmapOrder, KmapPath, Name, SeqMap,
-    SeqType, Sequence, SpellingTable, Validate, Wordlike,
+    KeyDefs, KeyPress, KmapOrder, KmapPath, Name, SeqMap, SeqType, Sequence,
+    SequenceEncoder, SpellingTable, Validate, Word, Wordlike,
 };
-use util::read_file;
 
 /// This handles all the details of building an AllData struct from
 /// configuration files. It loads sequences and chords, generates word chords,
-/// generates huffman encodings, and so on.
-pub fn load_all_data(settings_path: &PathBuf) -> Result<AllData, Error> {
-    let settings: Settings = serde_yaml::from_str(&read_file(settings_path)?)?;
+/// generates huffman encodings, and so on. If a profile is given, its options
+/// and modes override the ones at the top of the settings file.
+pub fn load_all_data(
+    settings_path: &PathBuf,
+    profile: Option<&str>,
+) -> Result<AllData, Error> {
+    let settings = Settings::load(settings_path, profile)?;
     settings.validate()?;
+    settings
+        .options
+        .validate_board()
+        .context("Settings don't match 'board_name'")?;
 
     let mut chords = load_chords(&settings).context("Failed to load chords")?;
 
@@ -34,20 +39,28 @@ pub fn load_all_data(settings_path: &PathBuf) -> Result<AllData, Error> {
     let spellings = make_spelling_table(&settings)
         .context("Failed to make spelling table")?;
 
-    load_dictionary(&settings, &spellings, &mut chords, &mut sequences)
-        .context("Failed to load dictionary")?;
+    let dictionary =
+        load_dictionary(&settings, &spellings, &mut chords, &mut sequences)
+            .context("Failed to load dictionary")?;
 
     let commands = load_commands(&settings, &mut sequences)
         .context("Failed to load commands")?;
 
-    let huffman_table = HuffmanTable::new(sequences.all_keypresses())
-        .context("Failed to make table of huffman encodings")?;
+    let frequencies = settings.options.load_huffman_corpus()?;
+    let encoder = SequenceEncoder::new(
+        settings.options.sequence_encoding,
+        &sequences,
+        &dictionary,
+        frequencies.as_ref(),
+    )
+    .context("Failed to make sequence encoder")?;
 
     Ok(AllData {
-        huffman_table,
+        encoder,
         commands,
         chords,
         sequences,
+        dictionary,
         word_mods: settings.word_modifiers.clone(),
         anagram_mods: settings.anagram_modifiers.clone(),
         plain_mods: settings.plain_modifiers.keys().cloned().collect(),
@@ -55,7 +68,6 @@ pub fn load_all_data(settings_path: &PathBuf) -> Result<AllData, Error> {
         spellings,
         chord_spec: settings.options.chord_spec()?,
         output_directory: settings.options.output_directory.clone(),
-        board: settings.options.board_name.clone(),
         user_options: settings.options,
     })
 }
@@ -103,14 +115,39 @@ fn make_spelling_table(settings: &Settings) -> Result<SpellingTable, Error> {
     Ok(SpellingTable(table))
 }
 
+/// Add chords and sequences for all the words in the `dictionary` section and
+/// the word lists, and for all the snippets. Return the full dictionary.
 fn load_dictionary(
     settings: &Settings,
     spellings: &SpellingTable,
     chords: &mut AllChordMaps,
     sequences: &mut AllSeqMaps,
-) -> Result<(), Error> {
+) -> Result<Vec<Word>, Error> {
+    let mut dictionary = settings.dictionary.clone();
+    let mut origins: BTreeMap<Name, String> = dictionary
+        .iter()
+        .map(|word| (word.name(), "the 'dictionary' section".to_owned()))
+        .collect();
+    for path in &settings.word_lists {
+        for (line, word) in Word::load_word_list(path)? {
+            let origin = format!("{:?}, line {}", path, line);
+            if let Some(first) = origins.get(&word.name()) {
+                return Err(Error::ConflictErr {
+                    key: word.name().to_string(),
+                    container: "dictionary".to_owned(),
+                }
+                .context(&format!(
+                    "Defined in both {} and {}",
+                    first, origin
+                )));
+            }
+            origins.insert(word.name(), origin);
+            dictionary.push(word);
+        }
+    }
+
     for kmap in settings.kmaps_with_words() {
-        for word_info in &settings.dictionary {
+        for word_info in &dictionary {
             add_wordlike(
                 word_info,
                 kmap.to_owned(),
@@ -135,7 +172,7 @@ fn load_dictionary(
             })?;
         }
     }
-    Ok(())
+    Ok(dictionary)
 }
 
 fn add_wordlike<T>(
@@ -156,7 +193,8 @@ where
     chords.insert(name, chord, kmap)
 }
 
-fn make_wordlike_chord<T>(
+/// Make a word's chord from the union of its letters' chords in the kmap.
+pub fn make_wordlike_chord<T>(
     wordlike: &T,
     kmap: &KmapPath,
     spelling_table: &SpellingTable,
@@ -0,0 +1,200 @@
+//! Let the large sections of the settings file, like the dictionary, be split
+//! into separate files. Included files are merged into the main settings before
+//! they're deserialized, so the rest of the loading code doesn't need to know
+//! where each entry came from.
+
+use serde_yaml::{self, Mapping, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::PathBuf;
+
+use error::{Error, ResultExt};
+use input::settings::key;
+use util::read_file;
+
+pub const INCLUDE_KEY: &str = "include";
+
+/// The sections that can be moved into included files.
+const INCLUDABLE_SECTIONS: [&str; 4] =
+    ["dictionary", "macros", "snippets", "modes"];
+
+const INCLUDE_EXTENSIONS: [&str; 2] = ["yaml", "yml"];
+
+/// The file that each entry came from, by section and entry.
+type Origins = HashMap<(String, String), PathBuf>;
+
+/// Remove the list of included files or directories from the settings, and
+/// merge the contents of those files into the settings instead. Paths are
+/// relative to the current directory, like kmap paths. Directories include all
+/// the yaml files in them, in alphabetical order.
+pub fn merge_includes(
+    settings: &mut Mapping,
+    settings_path: &PathBuf,
+) -> Result<(), Error> {
+    let includes = match settings.remove(&key(INCLUDE_KEY)) {
+        None | Some(Value::Null) => return Ok(()),
+        Some(Value::Sequence(includes)) => includes,
+        Some(Value::String(include)) => vec![Value::String(include)],
+        Some(_) => {
+            return Err(Error::BadValueErr {
+                thing: "include".to_owned(),
+                value: "expected a list of paths".to_owned(),
+            })
+        }
+    };
+
+    let mut origins = Origins::new();
+    for section in &INCLUDABLE_SECTIONS {
+        if let Some(entries) = settings.get(&key(section)) {
+            record_origins(section, entries, settings_path, &mut origins)?;
+        }
+    }
+
+    for include in includes {
+        let path = match include {
+            Value::String(path) => PathBuf::from(path),
+            _ => {
+                return Err(Error::BadValueErr {
+                    thing: "include path".to_owned(),
+                    value: format!("{:?}", include),
+                })
+            }
+        };
+        for file in expand_include(&path)? {
+            merge_file(settings, &file, &mut origins).with_context(|| {
+                format!("Failed to include settings file: {:?}", file)
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn merge_file(
+    settings: &mut Mapping,
+    path: &PathBuf,
+    origins: &mut Origins,
+) -> Result<(), Error> {
+    let sections = match serde_yaml::from_str(&read_file(path)?)? {
+        Value::Mapping(sections) => sections,
+        Value::Null => return Ok(()),
+        _ => return Err(Error::Empty("included settings file".to_owned())),
+    };
+
+    for (section, entries) in sections {
+        let name = section.as_str().unwrap_or_default().to_owned();
+        if !INCLUDABLE_SECTIONS.contains(&name.as_str()) {
+            return Err(Error::BadValueErr {
+                thing: "section in included file (only dictionary, macros, \
+                        snippets, and modes can be included)"
+                    .to_owned(),
+                value: name,
+            });
+        }
+        record_origins(&name, &entries, path, origins)?;
+        match (settings.get_mut(&section), entries) {
+            (Some(Value::Sequence(base)), Value::Sequence(entries)) => {
+                base.extend(entries)
+            }
+            (Some(Value::Mapping(base)), Value::Mapping(entries)) => {
+                base.extend(entries)
+            }
+            (None, entries) => {
+                settings.insert(section, entries);
+            }
+            _ => {
+                return Err(Error::BadValueErr {
+                    thing: "section in included file (it has a different \
+                            type than in the main settings)"
+                        .to_owned(),
+                    value: name,
+                })
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Remember which file each entry came from, and return an error naming both
+/// files if it was already defined in another file. Entries that are repeated
+/// within one file are left for the rest of the loading code to report.
+fn record_origins(
+    section: &str,
+    entries: &Value,
+    path: &PathBuf,
+    origins: &mut Origins,
+) -> Result<(), Error> {
+    let keys: BTreeSet<String> = match entries {
+        Value::Mapping(entries) => entries
+            .iter()
+            .map(|(name, _)| entry_key(section, name))
+            .collect::<Result<_, _>>()?,
+        Value::Sequence(entries) => entries
+            .iter()
+            .map(|entry| entry_key(section, entry))
+            .collect::<Result<_, _>>()?,
+        _ => BTreeSet::new(),
+    };
+    for entry in keys {
+        let origin_key = (section.to_owned(), entry);
+        if let Some(first) = origins.get(&origin_key) {
+            return Err(Error::ConflictErr {
+                key: origin_key.1.clone(),
+                container: section.to_owned(),
+            }
+            .context(&format!("Defined in both {:?} and {:?}", first, path)));
+        }
+        origins.insert(origin_key, path.to_owned());
+    }
+    Ok(())
+}
+
+/// A string identifying an entry, like a macro's name or a dictionary word.
+/// Entries in list sections are identified by their main field, so that the
+/// same word with different options still counts as the same entry.
+fn entry_key(section: &str, entry: &Value) -> Result<String, Error> {
+    let field = match section {
+        "dictionary" => "word",
+        "snippets" => "snippet",
+        _ => return value_key(entry),
+    };
+    match entry {
+        Value::Mapping(fields) => match fields.get(&key(field)) {
+            Some(name) => value_key(name),
+            None => value_key(entry),
+        },
+        _ => value_key(entry),
+    }
+}
+
+fn value_key(value: &Value) -> Result<String, Error> {
+    match value {
+        Value::String(name) => Ok(name.to_owned()),
+        _ => Ok(serde_yaml::to_string(value)?
+            .trim_start_matches("---")
+            .trim()
+            .to_owned()),
+    }
+}
+
+fn expand_include(path: &PathBuf) -> Result<Vec<PathBuf>, Error> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_owned()]);
+    }
+    let mut files = Vec::new();
+    let entries = fs::read_dir(path).with_context(|| {
+        format!("Failed to read included directory: {:?}", path)
+    })?;
+    for entry in entries {
+        let file = entry?.path();
+        let is_yaml = file
+            .extension()
+            .and_then(OsStr::to_str)
+            .map(|extension| INCLUDE_EXTENSIONS.contains(&extension));
+        if file.is_file() && is_yaml == Some(true) {
+            files.push(file);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,17 @@
+use serde_yaml;
+use std::path::PathBuf;
+
+use error::{Error, ResultExt};
+use types::{KmapConversion, Validate};
+use util::read_file;
+
+impl KmapConversion {
+    pub fn load(path: &PathBuf) -> Result<Self, Error> {
+        let text = read_file(path).with_context(|| {
+            format!("Failed to read kmap conversion: {:?}", path)
+        })?;
+        let conversion: KmapConversion = serde_yaml::from_str(&text)?;
+        conversion.validate()?;
+        Ok(conversion)
+    }
+}
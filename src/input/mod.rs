@@ -9,9 +9,13 @@
 //! like it should be contained in this module, we need to add the derive
 //! attributes to the struct definition itself.
 
-pub use self::all_data_builder::load_all_data;
+pub use self::all_data_builder::{load_all_data, make_wordlike_chord};
 use self::settings::Settings;
 
 mod all_data_builder;
+mod include;
+mod kmap_conversion;
 mod parse_kmap;
 mod settings;
+mod word_frequencies;
+mod word_list;
@@ -1,15 +1,18 @@
 ///! For parsing kmap files to get chords
-use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
 use error::{Error, ResultExt};
-use types::{Chord, KmapFormat, KmapOrder, KmapPath, Name};
+use types::{
+    Chord, ChordMap, KmapFile, KmapFormat, KmapItem, KmapLayout, KmapOrder,
+    KmapPath, KmapSection, Name, SourceSpan,
+};
 
-const COMMENT_START: char = '#';
-const UNPRESSED_CHAR: char = '.';
-const PRESSED_CHAR: char = '*';
-const BLANK_MAPPING: &str = "blank_mapping";
+const COMMENT_START: char = KmapFile::COMMENT_START;
+const UNPRESSED_CHAR: char = KmapFile::UNPRESSED_CHAR;
+const PRESSED_CHAR: char = KmapFile::PRESSED_CHAR;
+const BLANK_MAPPING: &str = KmapFile::BLANK_MAPPING;
+const TAB_WIDTH: usize = 8;
 
 struct Section {
     line_num: usize,
@@ -21,10 +24,7 @@ struct Section {
 ////////////////////////////////////////////////////////////////////////////////
 
 impl KmapPath {
-    pub fn read(
-        &self,
-        format: &KmapFormat,
-    ) -> Result<BTreeMap<Name, Chord<KmapOrder>>, Error> {
+    pub fn read(&self, format: &KmapFormat) -> Result<ChordMap, Error> {
         let lines = self
             .load_lines()?
             .into_iter()
@@ -39,11 +39,72 @@ impl KmapPath {
         let mut mappings = Vec::new();
         for chunk in lines.chunks(format.block_length()) {
             let section = Section::new(chunk, format)?;
-            mappings.extend(section.mappings()?);
+            mappings.extend(section.mappings(self)?);
         }
         to_chord_map(mappings)
     }
 
+    /// Read the kmap file along with its comments and the layout of its first
+    /// chord, so that it can be written back out. Comments in the middle of a
+    /// section are moved before it.
+    pub fn read_file(&self, format: &KmapFormat) -> Result<KmapFile, Error> {
+        let mut items = Vec::new();
+        let mut layout = None;
+        let mut comment = Vec::new();
+        let mut hoisted = Vec::new();
+        let mut block_lines: Vec<(usize, String)> = Vec::new();
+
+        for (i, raw_line) in self.load_lines()?.into_iter().enumerate().skip(1)
+        {
+            let line = raw_line.trim();
+            if line.starts_with(COMMENT_START) {
+                comment.push(line.to_owned());
+                continue;
+            }
+            if !comment.is_empty() {
+                let paragraph = KmapItem::Comment(comment.split_off(0));
+                if block_lines.is_empty() {
+                    items.push(paragraph);
+                } else {
+                    hoisted.push(paragraph);
+                }
+            }
+            if line.is_empty() {
+                continue;
+            }
+
+            block_lines.push(check_ascii(i, raw_line)?);
+            if block_lines.len() == format.block_length() {
+                if layout.is_none() {
+                    layout = Some(read_layout(&block_lines, format));
+                }
+                let trimmed: Vec<_> = block_lines
+                    .drain(..)
+                    .map(|(i, l)| (i, l.trim().to_owned()))
+                    .collect();
+                let section = Section::new(&trimmed, format)?;
+                items.append(&mut hoisted);
+                items.push(KmapItem::Section(KmapSection {
+                    chords: section.chords()?,
+                    names: section.names,
+                }));
+            }
+        }
+        if !block_lines.is_empty() {
+            // This will fail, and report the wrong number of lines.
+            Section::new(&block_lines, format)?;
+        }
+        items.append(&mut hoisted);
+        if !comment.is_empty() {
+            items.push(KmapItem::Comment(comment));
+        }
+
+        Ok(KmapFile {
+            layout: layout.unwrap_or_else(|| KmapLayout::plain(format)),
+            items,
+        })
+    }
+
     fn load_lines(&self) -> Result<Vec<String>, Error> {
         let buf = BufReader::new(File::open(&self.0)?);
         let mut lines: Vec<_> = buf.lines().map(|w| w.unwrap()).collect();
@@ -85,10 +146,28 @@ impl Section {
         })
     }
 
-    /// Get all Name -> Chord mappings from the section.
-    fn mappings(self) -> Result<Vec<(Name, Chord<KmapOrder>)>, Error> {
+    /// Get all Name -> Chord mappings from the section, along with where
+    /// each chord was defined.
+    fn mappings(
+        self,
+        kmap: &KmapPath,
+    ) -> Result<Vec<(Name, Chord<KmapOrder>, SourceSpan)>, Error> {
         let chords = self.chords()?;
-        Ok(self.names.into_iter().zip(chords.into_iter()).collect())
+        let line = self.line_num;
+        Ok(self
+            .names
+            .into_iter()
+            .zip(chords)
+            .enumerate()
+            .map(|(i, (name, chord))| {
+                let span = SourceSpan {
+                    kmap: kmap.to_owned(),
+                    line,
+                    block: i + 1,
+                };
+                (name, chord, span)
+            })
+            .collect())
     }
 
     /// Extract the Chords from the section, in the same order as their
@@ -193,22 +272,63 @@ impl Section {
     }
 }
 
+/// Get the spacing of the first chord in the block, relative to the start of
+/// the names line.
+fn read_layout(
+    block_lines: &[(usize, String)],
+    format: &KmapFormat,
+) -> KmapLayout {
+    let lines: Vec<_> = block_lines
+        .iter()
+        .map(|(_, line)| expand_tabs(line))
+        .collect();
+    let indent = lines[0].len() - lines[0].trim_start().len();
+    let layout = lines[1..]
+        .iter()
+        .zip(format.switches_per_line())
+        .map(|(line, num_switches)| {
+            let mut chord = String::new();
+            let mut remaining = num_switches;
+            for c in line.chars().skip(indent) {
+                if remaining == 0 {
+                    break;
+                }
+                if c.is_whitespace() {
+                    chord.push(' ');
+                } else {
+                    chord.push(UNPRESSED_CHAR);
+                    remaining -= 1;
+                }
+            }
+            chord
+        })
+        .collect();
+    KmapLayout(layout)
+}
+
+fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::new();
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = TAB_WIDTH - expanded.len() % TAB_WIDTH;
+            expanded.push_str(&" ".repeat(spaces));
+        } else {
+            expanded.push(c);
+        }
+    }
+    expanded
+}
+
 fn to_chord_map(
-    mappings: Vec<(Name, Chord<KmapOrder>)>,
-) -> Result<BTreeMap<Name, Chord<KmapOrder>>, Error> {
-    // let blank_mapping = Name::from(BLANK_MAPPING);
-    let mut map = BTreeMap::new();
-    for (name, chord) in mappings {
+    mappings: Vec<(Name, Chord<KmapOrder>, SourceSpan)>,
+) -> Result<ChordMap, Error> {
+    let mut map = ChordMap::default();
+    for (name, chord, span) in mappings {
         if name.0 == BLANK_MAPPING {
             continue;
         }
-        if map.insert(name.clone(), chord).is_some() {
-            return Err(Error::ConflictErr {
-                key: name.to_string(),
-                container: "chord names".into(),
-            })
-            .context("Duplicate chord names in kmap file");
-        }
+        map.insert_with_span(name, chord, span)
+            .context("Duplicate chord names in kmap file")?;
     }
     Ok(map)
 }
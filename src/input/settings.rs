@@ -1,13 +1,19 @@
-use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::de::{
+    self, Deserialize, DeserializeOwned, Deserializer, SeqAccess, Visitor,
+};
+use serde_yaml::{self, Value};
 use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 use error::{Error, ResultExt};
+use input::include::{merge_includes, INCLUDE_KEY};
 use types::{
     Command, KeyPress, KmapPath, ModeInfo, ModeName, Name, Sequence, Snippet,
     UserOptions, Validate, Word,
 };
+use util::read_file;
 
 validated_struct! {
     #[derive(Deserialize, Debug)]
@@ -21,15 +27,72 @@ validated_struct! {
         pub word_modifiers: Vec<Name>,
         pub anagram_modifiers: Vec<Name>,
         pub commands: Vec<Command>,
+        #[serde(default)]
         pub dictionary: Vec<Word>,
+        /// Plain text dictionaries, loaded after the `dictionary` section.
+        #[serde(default)]
+        pub word_lists: Vec<PathBuf>,
         #[serde(default)]
         pub snippets: Vec<Snippet>,
     }
 }
 
+const PROFILES_KEY: &str = "profiles";
+
+/// The sections that a profile can override. Everything else, like the
+/// dictionary and macros, is shared between all profiles.
+const PROFILE_SECTIONS: [&str; 2] = ["options", "modes"];
+
 ////////////////////////////////////////////////////////////////////////////////
 
 impl Settings {
+    /// Load a settings file, merging in any files it includes. If a profile
+    /// name is given, the options in that profile replace the matching
+    /// top-level options, and its modes (if any) replace all the top-level
+    /// modes, including any from included files.
+    pub fn load(path: &PathBuf, profile: Option<&str>) -> Result<Self, Error> {
+        let source = read_file(path)?;
+        let mut value: Value = serde_yaml::from_str(&source)?;
+        let needs_merging = match value {
+            Value::Mapping(ref mapping) => {
+                mapping.contains_key(&key(INCLUDE_KEY))
+                    || mapping.contains_key(&key(PROFILES_KEY))
+            }
+            _ => false,
+        };
+        if !needs_merging && profile.is_none() {
+            // Deserializing the text directly keeps the location of any
+            // errors, which a `Value` doesn't have.
+            return Ok(serde_yaml::from_str(&source)?);
+        }
+
+        let profiles = match value {
+            Value::Mapping(ref mut mapping) => {
+                merge_includes(mapping, path)?;
+                mapping.remove(&key(PROFILES_KEY)).unwrap_or(Value::Null)
+            }
+            _ => Value::Null,
+        };
+        if let Some(name) = profile {
+            let overrides = match profiles {
+                Value::Mapping(mut profiles) => profiles.remove(&key(name)),
+                _ => None,
+            }
+            .ok_or_else(|| Error::LookupErr {
+                key: name.to_owned(),
+                container: "profiles".to_owned(),
+            })?;
+            apply_profile(&mut value, overrides)
+                .with_context(|| format!("Invalid profile: '{}'", name))?;
+        }
+        from_merged_value(value).with_context(|| {
+            format!(
+                "Invalid settings in {:?}, or in the files it includes",
+                path
+            )
+        })
+    }
+
     pub fn mode_names(&self) -> impl Iterator<Item = &ModeName> {
         self.modes.keys()
     }
@@ -51,6 +114,89 @@ impl Settings {
     }
 }
 
+pub(super) fn key(name: &str) -> Value {
+    Value::String(name.to_owned())
+}
+
+/// Deserialize settings that were merged from several files or a profile.
+/// Errors from a `Value` don't say where they happened, so each section is
+/// checked on its own first, to at least name the section.
+fn from_merged_value(value: Value) -> Result<Settings, Error> {
+    if let Value::Mapping(ref sections) = value {
+        for (section, entries) in sections {
+            let name = section.as_str().unwrap_or_default();
+            check_section(name, entries.to_owned())
+                .with_context(|| format!("Invalid section: '{}'", name))?;
+        }
+    }
+    Ok(serde_yaml::from_value(value)?)
+}
+
+fn check_section(name: &str, entries: Value) -> Result<(), Error> {
+    match name {
+        "options" => check::<UserOptions>(entries),
+        "modes" => check::<BTreeMap<ModeName, ModeInfo>>(entries),
+        "plain_modifiers" | "plain_keys" => {
+            check::<BTreeMap<Name, KeyPress>>(entries)
+        }
+        "macros" => check::<BTreeMap<Name, Sequence>>(entries),
+        "word_modifiers" | "anagram_modifiers" => check::<Vec<Name>>(entries),
+        "commands" => check::<Vec<Command>>(entries),
+        "dictionary" => check::<Vec<Word>>(entries),
+        "word_lists" => check::<Vec<PathBuf>>(entries),
+        "snippets" => check::<Vec<Snippet>>(entries),
+        // Unknown sections are reported when deserializing the whole file.
+        _ => Ok(()),
+    }
+}
+
+fn check<T: DeserializeOwned>(entries: Value) -> Result<(), Error> {
+    serde_yaml::from_value::<T>(entries)?;
+    Ok(())
+}
+
+fn apply_profile(settings: &mut Value, overrides: Value) -> Result<(), Error> {
+    let settings = match settings {
+        Value::Mapping(settings) => settings,
+        _ => return Err(Error::Empty("settings file".to_owned())),
+    };
+    let overrides = match overrides {
+        Value::Mapping(overrides) => overrides,
+        Value::Null => return Ok(()),
+        _ => {
+            return Err(Error::BadValueErr {
+                thing: "profile".to_owned(),
+                value: "expected a map of sections".to_owned(),
+            })
+        }
+    };
+
+    for (section, value) in overrides {
+        let name = section.as_str().unwrap_or_default().to_owned();
+        if !PROFILE_SECTIONS.contains(&name.as_str()) {
+            return Err(Error::BadValueErr {
+                thing: "profile section (only options and modes can be \
+                        overridden)"
+                    .to_owned(),
+                value: name,
+            });
+        }
+        match (settings.get_mut(&section), value) {
+            (Some(Value::Mapping(base)), Value::Mapping(value))
+                if name == "options" =>
+            {
+                for (option, option_value) in value {
+                    base.insert(option, option_value);
+                }
+            }
+            (_, value) => {
+                settings.insert(section, value);
+            }
+        }
+    }
+    Ok(())
+}
+
 impl<'de> Deserialize<'de> for Sequence {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -0,0 +1,97 @@
+use std::path::PathBuf;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use error::{Error, ResultExt};
+use types::{UserOptions, WordFrequencies};
+use util::read_file;
+
+const COMMENT_START: char = '#';
+
+impl WordFrequencies {
+    /// Load a file listing one word per line, from most to least common.
+    /// Anything after the first word on a line is ignored, as are empty lines
+    /// and comments.
+    pub fn load_ranking(path: &PathBuf) -> Result<Self, Error> {
+        let text = read_file(path).with_context(|| {
+            format!("Failed to read word ranking file: {:?}", path)
+        })?;
+        let words: Vec<String> = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with(COMMENT_START))
+            .filter_map(|line| line.split_whitespace().next())
+            .map(str::to_owned)
+            .collect();
+        if words.is_empty() {
+            return Err(Error::Empty("word ranking file".into()));
+        }
+        Ok(Self::from_ranking(words))
+    }
+
+    /// Load a frequency list, with a word and the number of times it occurs on
+    /// each line (like `the 23135851162`). Empty lines and comments are
+    /// ignored.
+    pub fn load_frequency_list(path: &PathBuf) -> Result<Self, Error> {
+        let text = read_file(path).with_context(|| {
+            format!("Failed to read word frequency file: {:?}", path)
+        })?;
+        let counts = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with(COMMENT_START))
+            .map(parse_count)
+            .collect::<Result<Vec<_>, Error>>()
+            .with_context(|| {
+                format!("Failed to parse word frequency file: {:?}", path)
+            })?;
+        if counts.is_empty() {
+            return Err(Error::Empty("word frequency file".into()));
+        }
+        Ok(Self::from_counts(counts))
+    }
+
+    /// Count how many times each word occurs in a plain text file.
+    pub fn load_corpus(path: &PathBuf) -> Result<Self, Error> {
+        let text = read_file(path).with_context(|| {
+            format!("Failed to read corpus file: {:?}", path)
+        })?;
+        let counts: Vec<_> = text
+            .unicode_words()
+            .map(|word| (word.to_owned(), 1))
+            .collect();
+        if counts.is_empty() {
+            return Err(Error::Empty("corpus file".into()));
+        }
+        Ok(Self::from_counts(counts))
+    }
+}
+
+impl UserOptions {
+    /// Load the `huffman_corpus`, if there is one.
+    pub fn load_huffman_corpus(
+        &self,
+    ) -> Result<Option<WordFrequencies>, Error> {
+        match self.huffman_corpus {
+            Some(ref path) => Ok(Some(WordFrequencies::load_corpus(path)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+fn parse_count(line: &str) -> Result<(String, u64), Error> {
+    let bad_line = || Error::BadValueErr {
+        thing: "word frequency line".into(),
+        value: line.into(),
+    };
+    let mut tokens = line.split_whitespace();
+    let word = tokens.next().ok_or_else(bad_line)?;
+    let count = tokens
+        .next()
+        .and_then(|count| count.parse().ok())
+        .ok_or_else(bad_line)?;
+    if tokens.next().is_some() {
+        return Err(bad_line());
+    }
+    Ok((word.to_owned(), count))
+}
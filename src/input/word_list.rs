@@ -0,0 +1,62 @@
+use std::path::PathBuf;
+
+use error::{Error, ResultExt};
+use types::{AnagramNum, Validate, Word};
+use util::read_file;
+
+const COMMENT_START: char = '#';
+
+impl Word {
+    /// Load a plain text dictionary, with one word per line. Each word can be
+    /// followed by an anagram number and then an alternate chord, separated by
+    /// whitespace (like `acknowledge 0 ack`). Since the columns are separated
+    /// by whitespace, words and chords can't contain spaces; entries like that
+    /// have to go in the `dictionary` section instead. Empty lines and
+    /// comments are ignored. Each word is returned with its line number.
+    pub fn load_word_list(path: &PathBuf) -> Result<Vec<(usize, Word)>, Error> {
+        let text = read_file(path)
+            .with_context(|| format!("Failed to read word list: {:?}", path))?;
+        let mut words = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(COMMENT_START) {
+                continue;
+            }
+            let word = Word::parse_word_list_line(line).with_context(|| {
+                format!("Invalid entry in word list {:?}, line {}", path, i + 1)
+            })?;
+            words.push((i + 1, word));
+        }
+        Ok(words)
+    }
+
+    fn parse_word_list_line(line: &str) -> Result<Word, Error> {
+        let mut columns = line.split_whitespace();
+        let word = columns.next().expect("word list line is empty").to_owned();
+        let anagram = match columns.next() {
+            Some(column) => {
+                let num = column.parse().map_err(|_| Error::BadValueErr {
+                    thing: "anagram number".into(),
+                    value: column.into(),
+                })?;
+                Some(AnagramNum::new(num)?)
+            }
+            None => None,
+        };
+        let chord = columns.next().map(str::to_owned);
+        if let Some(extra) = columns.next() {
+            return Err(Error::BadValueErr {
+                thing: "extra column".into(),
+                value: extra.into(),
+            });
+        }
+
+        let word = Word {
+            word,
+            anagram,
+            chord,
+        };
+        word.validate()?;
+        Ok(word)
+    }
+}
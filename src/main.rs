@@ -3,6 +3,7 @@ extern crate cursive;
 extern crate itertools;
 extern crate natord;
 extern crate serde;
+extern crate serde_json;
 extern crate serde_yaml;
 extern crate svg;
 extern crate time;
@@ -21,6 +22,9 @@ mod error;
 mod input;
 mod arduino;
 mod cheatsheet;
+mod diff;
+mod emulator;
+mod optimizer;
 mod output;
 #[cfg(test)]
 mod tests;
@@ -29,13 +33,20 @@ mod util;
 
 use std::path::PathBuf;
 
-use arduino::ArduinoIDE;
+use arduino::Arduino;
 use cheatsheet::CheatSheet;
+use diff::SettingsDiff;
+use emulator::{load_events, render_text, Emulator};
 use error::{Error, ResultExt};
 use input::load_all_data;
+use optimizer::{Change, DictionaryOptimizer, Suggestion};
 use tutor::TutorApp;
+use types::{
+    AllData, ArduinoBackend, Diagnostic, DiagnosticFormat, KmapPath, ModeName,
+    WordFrequencies,
+};
 
-use clap::{Arg, ArgGroup};
+use clap::{Arg, ArgGroup, ArgMatches};
 
 fn run() -> Result<(), Error> {
     // Parse command-line arguments
@@ -61,13 +72,13 @@ fn run() -> Result<(), Error> {
             Arg::with_name("verify")
                 .short("v")
                 .long("verify")
-                .help("Verify the updated firmware, using the Arduino IDE"),
+                .help("Verify the updated firmware, using the Arduino IDE or arduino-cli"),
         ).arg(
             Arg::with_name("upload")
                 .short("u")
                 .long("upload")
                 // .requires("port")
-                .help("Upload the updated firmware to the keyboard, using the Arduino IDE"),
+                .help("Upload the updated firmware to the keyboard, using the Arduino IDE or arduino-cli"),
         ).arg(
             Arg::with_name("port")
                 .short("p")
@@ -75,8 +86,128 @@ fn run() -> Result<(), Error> {
                 .takes_value(true)
                 .value_name("port_name")
                 .help("Select the serial port over which to upload the updated firmware"),
+        ).arg(
+            Arg::with_name("arduino_backend")
+                .long("arduino-backend")
+                .takes_value(true)
+                .value_name("backend")
+                .possible_values(&["ide", "cli"])
+                .help("Build with the Arduino IDE or arduino-cli, overriding 'arduino_backend' in the settings file"),
+        ).arg(
+            Arg::with_name("arduino_executable")
+                .long("arduino-executable")
+                .takes_value(true)
+                .value_name("executable")
+                .help("Path to the Arduino IDE or arduino-cli executable, overriding 'arduino_executable' in the settings file"),
+        ).arg(
+            Arg::with_name("sketch_directory")
+                .long("sketch-directory")
+                .takes_value(true)
+                .value_name("directory")
+                .help("Directory containing the firmware sketch, overriding 'sketch_directory' in the settings file"),
+        ).arg(
+            Arg::with_name("emulate")
+                .short("e")
+                .long("emulate")
+                .takes_value(true)
+                .value_name("switch_events_file")
+                .help("Emulate the firmware, replaying the switch presses and releases in the given yaml file"),
+        ).arg(
+            Arg::with_name("export")
+                .long("export")
+                .takes_value(true)
+                .value_name("sketch_directory")
+                .help("Save a self-contained copy of the firmware sketch and its configuration in the given directory, instead of in the output directory. Use one directory per settings file"),
+        ).arg(
+            Arg::with_name("fmt_kmap")
+                .long("fmt-kmap")
+                .help("Rewrite the kmap files used by the settings in canonical form, with aligned blocks and consistent spacing"),
+        ).arg(
+            Arg::with_name("sort_kmap")
+                .long("sort-kmap")
+                .requires("fmt_kmap")
+                .help("Sort the blocks in each section of the kmap files by name, when using --fmt-kmap"),
+        ).arg(
+            Arg::with_name("transform_kmap")
+                .long("transform-kmap")
+                .takes_value(true)
+                .number_of_values(3)
+                .value_names(&["transform", "input_kmap", "output_kmap"])
+                .help("Save a copy of a kmap file with its switches rearranged by one of the 'kmap_transforms' in the settings file, like to mirror a layout"),
+        ).arg(
+            Arg::with_name("convert_kmap")
+                .long("convert-kmap")
+                .takes_value(true)
+                .number_of_values(3)
+                .value_names(&["conversion_file", "input_kmap", "output_kmap"])
+                .help("Save a copy of a kmap file for a keyboard with a different 'kmap_format', using the switch correspondence in the given yaml file"),
+        ).arg(
+            Arg::with_name("write_kmap")
+                .long("write-kmap")
+                .takes_value(true)
+                .number_of_values(2)
+                .value_names(&["mode", "output_kmap"])
+                .help("Save all the chords in a mode to a single kmap file, including the chords generated for dictionary words and snippets"),
+        ).arg(
+            Arg::with_name("diff")
+                .long("diff")
+                .takes_value(true)
+                .value_name("old_settings_file")
+                .help("Compare an older version of the settings file to this one, and list the chords, words, and macros that changed in each mode"),
+        ).arg(
+            Arg::with_name("memory_report")
+                .long("memory-report")
+                .help("Estimate how many bytes the generated lookup tables will take up in the firmware, for each kmap, sequence type, and lookup group. Pointer sizes and struct padding depend on the board_name"),
+        ).arg(
+            Arg::with_name("compare_encodings")
+                .long("compare-encodings")
+                .help("Estimate the size of the generated lookup tables with each sequence_encoding, to find the smallest one for these settings"),
+        ).arg(
+            Arg::with_name("check")
+                .long("check")
+                .help("Check the settings for problems like conflicting chords, without saving the firmware configuration. Exits with an error if any are found"),
+        ).arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .value_name("format")
+                .possible_values(&["text", "json"])
+                .requires("check")
+                .help("Select the output format of --check (default: text)"),
+        ).arg(
+            Arg::with_name("suggest_dictionary")
+                .long("suggest-dictionary")
+                .takes_value(true)
+                .value_name("output_file")
+                .help("Suggest anagram numbers and alternate chords that resolve conflicts between dictionary words, and save them as a new dictionary section"),
+        ).arg(
+            Arg::with_name("word_ranking")
+                .long("word-ranking")
+                .takes_value(true)
+                .value_name("word_ranking_file")
+                .help("File listing words from most to least common, one per line. More common words should get lower anagram numbers"),
+        ).arg(
+            Arg::with_name("word_frequencies")
+                .long("word-frequencies")
+                .takes_value(true)
+                .value_name("word_frequency_file")
+                .help("File listing a word and the number of times it occurs on each line, used like --word-ranking"),
+        ).arg(
+            Arg::with_name("corpus")
+                .long("corpus")
+                .takes_value(true)
+                .value_name("corpus_file")
+                .help("Plain text file to count word frequencies in, used like --word-ranking"),
+        ).group(
+            ArgGroup::with_name("frequencies").args(&["word_ranking", "word_frequencies", "corpus"]).multiple(false)
         ).group(
-            ArgGroup::with_name("commands").args(&["cheatsheet", "tutor", "verify","upload"]).multiple(false)
+            ArgGroup::with_name("commands").args(&["cheatsheet", "tutor", "verify","upload", "emulate", "check", "suggest_dictionary", "export", "fmt_kmap", "transform_kmap", "convert_kmap", "write_kmap", "diff", "memory_report", "compare_encodings"]).multiple(false)
+        ).arg(
+            Arg::with_name("profile")
+                .long("profile")
+                .takes_value(true)
+                .value_name("profile_name")
+                .help("Select a keyboard profile from the settings file, overriding its options and modes"),
         ).arg(
             Arg::with_name("settings")
                 .takes_value(true)
@@ -85,15 +216,123 @@ fn run() -> Result<(), Error> {
                 .help("Settings file that specifies the keymaps, dictionary, etc"),
         ).get_matches();
 
-    println!();
+    // Keep stdout parseable when printing diagnostics as JSON.
+    if args.value_of("format") != Some("json") {
+        println!();
+    }
 
     let settings_path = PathBuf::from(
         args.value_of("settings")
             .expect("settings file not specified"),
     );
 
-    let all_data = load_all_data(&settings_path)?;
-    all_data.check();
+    let mut all_data = load_all_data(&settings_path, args.value_of("profile"))?;
+    let frequencies = load_frequencies(&args)?;
+
+    if args.is_present("fmt_kmap") {
+        return all_data
+            .format_kmaps(args.is_present("sort_kmap"))
+            .context("Failed to format kmap files");
+    }
+
+    if let Some(mut values) = args.values_of("transform_kmap") {
+        let mut next =
+            || values.next().expect("missing --transform-kmap value");
+        let transform_name = next();
+        let input = KmapPath(next().to_owned());
+        let output = PathBuf::from(next());
+        return all_data
+            .transform_kmap(transform_name, &input, &output)
+            .context("Failed to transform kmap file");
+    }
+
+    if let Some(mut values) = args.values_of_os("convert_kmap") {
+        let mut next = || values.next().expect("missing --convert-kmap value");
+        let conversion_path = PathBuf::from(next());
+        let input = KmapPath(next().to_string_lossy().into_owned());
+        let output = PathBuf::from(next());
+        return all_data
+            .convert_kmap(&conversion_path, &input, &output)
+            .context("Failed to convert kmap file");
+    }
+
+    if let Some(mut values) = args.values_of("write_kmap") {
+        let mut next = || values.next().expect("missing --write-kmap value");
+        let mode = ModeName::from(next());
+        let output = PathBuf::from(next());
+        return all_data
+            .write_mode_kmap(&mode, &output)
+            .context("Failed to write kmap file");
+    }
+
+    if let Some(old_settings_path) = args.value_of_os("diff") {
+        let old_data = load_all_data(
+            &PathBuf::from(old_settings_path),
+            args.value_of("profile"),
+        )
+        .context("Failed to load old settings file")?;
+        print!("{}", SettingsDiff::new(&old_data, &all_data)?);
+        return Ok(());
+    }
+
+    if args.is_present("memory_report") {
+        print!("{}", all_data.memory_report()?);
+        return Ok(());
+    }
+
+    if args.is_present("compare_encodings") {
+        print!("{}", all_data.compare_encodings()?);
+        return Ok(());
+    }
+
+    if args.is_present("check") {
+        let format = DiagnosticFormat::from_name(
+            args.value_of("format").unwrap_or("text"),
+        )?;
+        let diagnostics = all_data.diagnostics(&frequencies);
+        print!("{}", Diagnostic::render_all(&diagnostics, format)?);
+        let errors = diagnostics.iter().filter(|d| d.is_error()).count();
+        if errors > 0 {
+            return Err(Error::CheckFailed { errors });
+        }
+        return Ok(());
+    }
+    all_data.check(&frequencies);
+
+    if let Some(directory) = args.value_of_os("export") {
+        all_data
+            .export(&PathBuf::from(directory))
+            .context("Failed to export firmware sketch")?;
+        return Ok(());
+    }
+
+    if let Some(events_path) = args.value_of_os("emulate") {
+        let events = load_events(&PathBuf::from(events_path))
+            .context("Failed to load switch events")?;
+        let outputs = Emulator::new(&all_data)?
+            .run(events)
+            .context("Failed to emulate firmware")?;
+        for output in &outputs {
+            println!("{}", output);
+        }
+        println!("\nTyped text:\n{}\n", render_text(&outputs));
+        return Ok(());
+    }
+
+    if let Some(output_path) = args.value_of_os("suggest_dictionary") {
+        let suggestions = DictionaryOptimizer::new(&all_data, &frequencies)?
+            .run()
+            .context("Failed to suggest dictionary")?;
+        for suggestion in &suggestions {
+            if suggestion.change != Change::Unchanged {
+                println!("{}: {}", suggestion.word.word, suggestion.change);
+            }
+        }
+        let path = PathBuf::from(output_path);
+        Suggestion::save_all(&suggestions, &path)?;
+        println!("\nSaved suggested dictionary to: {:?}", path);
+        return Ok(());
+    }
 
     if args.is_present("nosave") {
         println!("Not configuring the firmware (because --nosave was passed)");
@@ -120,27 +359,60 @@ fn run() -> Result<(), Error> {
     }
 
     if args.is_present("verify") {
-        let ide = ArduinoIDE::new(all_data.board());
+        let arduino = make_arduino(&args, &all_data)?;
         drop(all_data);
-        ide.verify()?;
+        arduino.verify()?;
         return Ok(());
     }
 
     if args.is_present("upload") {
-        let ide = ArduinoIDE::new(all_data.board());
+        let arduino = make_arduino(&args, &all_data)?;
         drop(all_data);
-        ide.upload(args.value_of("port"))?;
+        arduino.upload(args.value_of("port"))?;
         return Ok(());
     }
     Ok(())
 }
 
+/// Use the Arduino settings from the settings file, unless they were
+/// overridden on the command line.
+fn make_arduino(
+    args: &ArgMatches,
+    all_data: &AllData,
+) -> Result<Arduino, Error> {
+    let mut arduino = Arduino::new(&all_data.user_options);
+    if let Some(name) = args.value_of("arduino_backend") {
+        arduino = arduino.backend(ArduinoBackend::from_name(name)?);
+    }
+    if let Some(path) = args.value_of_os("arduino_executable") {
+        arduino = arduino.executable(PathBuf::from(path));
+    }
+    if let Some(path) = args.value_of_os("sketch_directory") {
+        arduino = arduino.sketch_directory(PathBuf::from(path));
+    }
+    Ok(arduino)
+}
+
+/// Load word frequencies from whichever source was passed, if any.
+fn load_frequencies(args: &ArgMatches) -> Result<WordFrequencies, Error> {
+    if let Some(path) = args.value_of_os("word_ranking") {
+        WordFrequencies::load_ranking(&PathBuf::from(path))
+    } else if let Some(path) = args.value_of_os("word_frequencies") {
+        WordFrequencies::load_frequency_list(&PathBuf::from(path))
+    } else if let Some(path) = args.value_of_os("corpus") {
+        WordFrequencies::load_corpus(&PathBuf::from(path))
+    } else {
+        Ok(WordFrequencies::default())
+    }
+}
+
 fn main() {
+    // Print status to stderr, so it doesn't get mixed into output that other
+    // tools might read, like `--check --format json`.
     if let Err(error) = run() {
-        println!("{}", error);
-        // TODO impl exit code on error?
+        eprintln!("{}", error);
         ::std::process::exit(1);
     } else {
-        println!("Done.");
+        eprintln!("Done.");
     }
 }
@@ -0,0 +1,295 @@
+//! Suggest anagram numbers and alternate chords for the dictionary words, so
+//! that no two words (or a word and any other chord) end up sharing the same
+//! chord in any kmap that uses words.
+
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use error::{Error, ResultExt};
+use input::make_wordlike_chord;
+use types::{
+    AllData, AnagramNum, Chord, KmapOrder, KmapPath, Name, Word,
+    WordFrequencies, Wordlike,
+};
+
+/// Shorter alternate chords are too easy to collide with other chords.
+const MIN_ALTERNATE_CHORD_LEN: usize = 2;
+
+pub struct DictionaryOptimizer<'a> {
+    data: &'a AllData,
+    frequencies: &'a WordFrequencies,
+    kmaps: Vec<&'a KmapPath>,
+    /// The chords that are already taken in each kmap, including their anagram
+    /// numbers.
+    occupied: HashSet<(&'a KmapPath, Chord<KmapOrder>)>,
+    max_anagram: AnagramNum,
+}
+
+/// The suggested dictionary entry for a single word.
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub word: Word,
+    pub change: Change,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Unchanged,
+    Anagram {
+        old: AnagramNum,
+        new: AnagramNum,
+    },
+    AlternateChord {
+        chord: String,
+        anagram: AnagramNum,
+    },
+    /// Every anagram number and alternate chord we tried was already taken.
+    Unresolved,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+impl<'a> DictionaryOptimizer<'a> {
+    pub fn new(
+        data: &'a AllData,
+        frequencies: &'a WordFrequencies,
+    ) -> Result<Self, Error> {
+        let kmaps: Vec<_> = data.kmaps_with_words().into_iter().collect();
+        if kmaps.is_empty() {
+            return Err(Error::Empty("set of kmaps that use words".into()));
+        }
+
+        // Start with every chord that isn't a dictionary word, since those
+        // can't be moved.
+        let word_names: HashSet<Name> =
+            data.dictionary.iter().map(Wordlike::name).collect();
+        let mut occupied = HashSet::new();
+        for &kmap in &kmaps {
+            for (name, chord) in data.chords.get_kmap(kmap)?.iter() {
+                if !word_names.contains(name) {
+                    occupied.insert((kmap, chord.to_owned()));
+                }
+            }
+        }
+
+        Ok(Self {
+            data,
+            frequencies,
+            kmaps,
+            occupied,
+            // Anagrams past the anagram mods can still be reached by cycling.
+            max_anagram: AnagramNum::new(AnagramNum::max_allowed())?,
+        })
+    }
+
+    /// Assign chords to words from most to least frequent, so the most common
+    /// words get the lowest anagram numbers. Words with the same frequency keep
+    /// their current relative order. The suggestions are returned in the same
+    /// order as the dictionary.
+    pub fn run(mut self) -> Result<Vec<Suggestion>, Error> {
+        let dictionary = &self.data.dictionary;
+        let mut order: Vec<usize> = (0..dictionary.len()).collect();
+        order.sort_by_key(|&i| {
+            let word = &dictionary[i];
+            (
+                Reverse(self.frequencies.get(&word.word)),
+                word.anagram_num(),
+                i,
+            )
+        });
+
+        let mut suggestions = vec![None; dictionary.len()];
+        for i in order {
+            let word = &dictionary[i];
+            suggestions[i] = Some(self.assign(word).with_context(|| {
+                format!("Failed to assign chord for word: {}", word.word)
+            })?);
+        }
+        Ok(suggestions
+            .into_iter()
+            .map(|s| s.expect("word was not assigned"))
+            .collect())
+    }
+
+    fn assign(&mut self, word: &Word) -> Result<Suggestion, Error> {
+        let chords = self.chords(word)?;
+        if let Some(anagram) = self.lowest_free_anagram(&chords) {
+            self.occupy(&chords, anagram);
+            let change = if anagram == word.anagram_num() {
+                Change::Unchanged
+            } else {
+                Change::Anagram {
+                    old: word.anagram_num(),
+                    new: anagram,
+                }
+            };
+            return Ok(Suggestion {
+                word: with_chord(word, word.chord.clone(), anagram),
+                change,
+            });
+        }
+
+        for alternate in alternate_chord_strings(word) {
+            let candidate = with_chord(
+                word,
+                Some(alternate.clone()),
+                AnagramNum::default(),
+            );
+            // Some prefixes can't be spelled with the kmap's letters.
+            let chords = match self.chords(&candidate) {
+                Ok(chords) => chords,
+                Err(_) => continue,
+            };
+            if let Some(anagram) = self.lowest_free_anagram(&chords) {
+                self.occupy(&chords, anagram);
+                return Ok(Suggestion {
+                    word: with_chord(word, Some(alternate.clone()), anagram),
+                    change: Change::AlternateChord {
+                        chord: alternate,
+                        anagram,
+                    },
+                });
+            }
+        }
+
+        Ok(Suggestion {
+            word: word.to_owned(),
+            change: Change::Unresolved,
+        })
+    }
+
+    /// The word's chord in each kmap, without its anagram number.
+    fn chords(&self, word: &Word) -> Result<Vec<Chord<KmapOrder>>, Error> {
+        self.kmaps
+            .iter()
+            .map(|kmap| {
+                let mut chord = make_wordlike_chord(
+                    word,
+                    kmap,
+                    &self.data.spellings,
+                    &self.data.chords,
+                )?;
+                chord.anagram_num = AnagramNum::default();
+                Ok(chord)
+            })
+            .collect()
+    }
+
+    fn lowest_free_anagram(
+        &self,
+        chords: &[Chord<KmapOrder>],
+    ) -> Option<AnagramNum> {
+        self.max_anagram.up_to().find(|&anagram| {
+            self.kmaps.iter().zip(chords).all(|(&kmap, chord)| {
+                let key = (kmap, with_anagram(chord, anagram));
+                !self.occupied.contains(&key)
+            })
+        })
+    }
+
+    fn occupy(&mut self, chords: &[Chord<KmapOrder>], anagram: AnagramNum) {
+        for (&kmap, chord) in self.kmaps.iter().zip(chords) {
+            self.occupied.insert((kmap, with_anagram(chord, anagram)));
+        }
+    }
+}
+
+impl Suggestion {
+    /// Format the suggestions as a `dictionary` section for the settings file.
+    pub fn render_all(suggestions: &[Suggestion]) -> String {
+        let mut s = String::from("dictionary:\n");
+        for suggestion in suggestions {
+            s += &format!("  - {}\n", render_word(&suggestion.word));
+        }
+        s
+    }
+
+    pub fn save_all(
+        suggestions: &[Suggestion],
+        path: &PathBuf,
+    ) -> Result<(), Error> {
+        let mut file = File::create(path)?;
+        file.write_all(Self::render_all(suggestions).as_bytes())
+            .with_context(|| {
+                format!("Failed to save suggested dictionary: {:?}", path)
+            })
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Unchanged => write!(f, "unchanged"),
+            Change::Anagram { old, new } => {
+                write!(f, "anagram {} -> {}", old, new)
+            }
+            Change::AlternateChord { chord, anagram } => {
+                write!(f, "alternate chord \"{}\", anagram {}", chord, anagram)
+            }
+            Change::Unresolved => write!(f, "unresolved conflict"),
+        }
+    }
+}
+
+/// Prefixes of the word's chord string, from longest to shortest, like the
+/// hand-picked alternate chords in the example settings (eg. "ack" for
+/// "acknowledge").
+fn alternate_chord_strings(word: &Word) -> Vec<String> {
+    let chord_string = word.chord_string();
+    let letters: Vec<_> = chord_string.graphemes(true).collect();
+    (MIN_ALTERNATE_CHORD_LEN..letters.len())
+        .rev()
+        .map(|len| letters[..len].concat())
+        .collect()
+}
+
+fn with_chord(word: &Word, chord: Option<String>, anagram: AnagramNum) -> Word {
+    Word {
+        word: word.word.clone(),
+        chord,
+        anagram: if anagram.is_default() {
+            None
+        } else {
+            Some(anagram)
+        },
+    }
+}
+
+fn with_anagram(
+    chord: &Chord<KmapOrder>,
+    anagram: AnagramNum,
+) -> Chord<KmapOrder> {
+    let mut chord = chord.to_owned();
+    chord.anagram_num = anagram;
+    chord
+}
+
+/// Format a word the same way as the dictionary entries in the example
+/// settings file.
+fn render_word(word: &Word) -> String {
+    let mut fields = vec![format!("word: {}", quote(&word.word))];
+    if let Some(ref chord) = word.chord {
+        fields.push(format!("chord: {}", quote(chord)));
+    }
+    if let Some(anagram) = word.anagram {
+        if !anagram.is_default() {
+            fields.push(format!("anagram: {}", anagram));
+        }
+    }
+    format!("{{{}}}", fields.join(", "))
+}
+
+fn quote(s: &str) -> String {
+    let escaped = s
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+        .replace('\t', r"\t");
+    format!("\"{}\"", escaped)
+}
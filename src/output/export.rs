@@ -0,0 +1,94 @@
+//! Export a self-contained copy of the firmware sketch, so that configurations
+//! for several keyboards can be built without overwriting each other.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use error::{Error, ResultExt};
+use types::AllData;
+
+/// Generated files are written fresh for each export, so any stale copies in
+/// the firmware sources are skipped.
+const GENERATED_FILE_PREFIX: &str = "auto_config";
+
+const SKETCH_EXTENSION: &str = "ino";
+
+impl AllData {
+    /// Copy the firmware sources into a new sketch directory, and save the
+    /// generated configuration there instead of in the output directory.
+    pub fn export(&self, directory: &PathBuf) -> Result<(), Error> {
+        let sketch_name = sketch_name(directory)?;
+        prepare_directory(directory, &sketch_name)?;
+
+        let source_directory = &self.user_options.firmware_source_directory;
+        let entries = fs::read_dir(source_directory).with_context(|| {
+            format!(
+                "Failed to read the firmware sources in {:?}. Set \
+                 'firmware_source_directory' to the pipit-firmware directory",
+                source_directory
+            )
+        })?;
+        for entry in entries {
+            let path = entry?.path();
+            if !path.is_file() {
+                continue;
+            }
+            let file_name = path
+                .file_name()
+                .expect("directory entry has no file name")
+                .to_string_lossy()
+                .into_owned();
+            if file_name.starts_with(GENERATED_FILE_PREFIX) {
+                continue;
+            }
+            // The Arduino tools require the sketch file to have the same name
+            // as its directory.
+            let is_sketch =
+                path.extension() == Some(OsStr::new(SKETCH_EXTENSION));
+            let destination = if is_sketch {
+                directory.join(format!("{}.{}", sketch_name, SKETCH_EXTENSION))
+            } else {
+                directory.join(&file_name)
+            };
+            fs::copy(&path, &destination).with_context(|| {
+                format!("Failed to copy firmware source: {:?}", path)
+            })?;
+        }
+
+        self.save_in(directory, "auto_config")?;
+        println!("Exported firmware sketch to: {:?}", directory);
+        Ok(())
+    }
+}
+
+fn sketch_name(directory: &Path) -> Result<String, Error> {
+    directory
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| Error::BadValueErr {
+            thing: "export directory".into(),
+            value: directory.to_string_lossy().into_owned(),
+        })
+}
+
+/// Create the export directory, refusing to write into an existing directory
+/// unless it's empty or was created by a previous export.
+fn prepare_directory(directory: &Path, sketch_name: &str) -> Result<(), Error> {
+    if directory.exists() {
+        let sketch_file =
+            directory.join(format!("{}.{}", sketch_name, SKETCH_EXTENSION));
+        let is_empty = fs::read_dir(directory)?.next().is_none();
+        if !is_empty && !sketch_file.is_file() {
+            return Err(Error::BadValueErr {
+                thing: "export directory (it isn't empty and doesn't contain \
+                        a previous export)"
+                    .into(),
+                value: directory.to_string_lossy().into_owned(),
+            });
+        }
+    }
+    fs::create_dir_all(directory).with_context(|| {
+        format!("Failed to create export directory: {:?}", directory)
+    })
+}
@@ -0,0 +1,340 @@
+//! Write kmap files back out in a canonical form, so that hand-edited keymaps
+//! stay consistent.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use error::{Error, ResultExt};
+use types::{
+    AllData, Chord, ChordMap, KmapConversion, KmapFile, KmapItem, KmapLayout,
+    KmapOrder, KmapPath, KmapSection, ModeName,
+};
+
+/// The number of spaces between the widest block and the next block.
+const BLOCK_SPACING: usize = 3;
+
+/// Sections of generated chords are split up to fit within this width, when
+/// possible.
+const MAX_LINE_WIDTH: usize = 80;
+
+impl AllData {
+    /// Rewrite every kmap file used by the settings in canonical form. Files
+    /// that are already formatted are left untouched.
+    pub fn format_kmaps(&self, sort: bool) -> Result<(), Error> {
+        let format = &self.user_options.kmap_format;
+        for kmap in self.kmap_paths() {
+            let original = fs::read_to_string(&kmap.0)?;
+            let formatted = kmap
+                .read_file(format)
+                .with_context(|| format!("Failed to read kmap: {}", kmap))?
+                .format(sort);
+            if formatted == original {
+                println!("Kmap is already formatted: {}", kmap);
+            } else {
+                write_kmap(Path::new(&kmap.0), &formatted)?;
+                println!("Formatted kmap: {}", kmap);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AllData {
+    /// Derive a new kmap file from an existing one, by rearranging the
+    /// switches of each chord with one of the `kmap_transforms` from the
+    /// settings.
+    pub fn transform_kmap(
+        &self,
+        transform_name: &str,
+        input: &KmapPath,
+        output: &Path,
+    ) -> Result<(), Error> {
+        let permutation = self.user_options.kmap_transform(transform_name)?;
+        input
+            .read_file(&self.user_options.kmap_format)
+            .with_context(|| format!("Failed to read kmap: {}", input))?
+            .transform(&permutation)?
+            .save(output)?;
+        println!("Saved transformed kmap to: {:?}", output);
+        Ok(())
+    }
+}
+
+impl AllData {
+    /// Convert a kmap file to the `kmap_format` of a different keyboard, and
+    /// list any chords that couldn't be converted.
+    pub fn convert_kmap(
+        &self,
+        conversion_path: &PathBuf,
+        input: &KmapPath,
+        output: &Path,
+    ) -> Result<(), Error> {
+        let conversion = KmapConversion::load(conversion_path)?;
+        let converter = conversion
+            .converter(&self.user_options.kmap_format)
+            .context("Invalid kmap conversion")?;
+        let (kmap_file, unconverted) = input
+            .read_file(&self.user_options.kmap_format)
+            .with_context(|| format!("Failed to read kmap: {}", input))?
+            .convert(&converter, &conversion.kmap_format)?;
+        kmap_file.save(output)?;
+
+        if !unconverted.is_empty() {
+            println!(
+                "These chords use switches that don't exist in the new \
+                 kmap_format, and were left out:"
+            );
+            for name in &unconverted {
+                println!("  {}", name);
+            }
+        }
+        println!("Saved converted kmap to: {:?}", output);
+        Ok(())
+    }
+}
+
+impl AllData {
+    /// Save every chord in the mode to a single kmap file, including the
+    /// chords generated for dictionary words and snippets. Anagram numbers
+    /// can't be written in kmap files, so the matching anagram modifier is
+    /// added to those chords instead. List any chords that couldn't be saved
+    /// because the mode has no such anagram modifier.
+    pub fn write_mode_kmap(
+        &self,
+        mode: &ModeName,
+        output: &Path,
+    ) -> Result<(), Error> {
+        if !self.modes.contains_key(mode) {
+            return Err(Error::LookupErr {
+                key: mode.to_string(),
+                container: "modes".to_owned(),
+            });
+        }
+        let mut chord_map = ChordMap::default();
+        let mut unwritten = Vec::new();
+        // Modifiers can be listed more than once.
+        let names: BTreeSet<_> = self.get_all_names().into_iter().collect();
+        for name in names {
+            let chord = match self.get_chord_in_mode(&name, mode) {
+                Some(chord) => chord,
+                None => continue,
+            };
+            let chord = match self.incorporate_anagram(chord, mode) {
+                Some(chord) => chord,
+                None => {
+                    unwritten.push(name);
+                    continue;
+                }
+            };
+            match self.get_span_in_mode(&name, mode) {
+                Some(span) => {
+                    chord_map.insert_with_span(name, chord, span.to_owned())?
+                }
+                None => chord_map.insert(name, chord)?,
+            }
+        }
+        chord_map
+            .to_kmap_file(KmapLayout::plain(&self.user_options.kmap_format))?
+            .save(output)?;
+
+        if !unwritten.is_empty() {
+            println!(
+                "These chords have anagram numbers without an anagram \
+                 modifier in this mode, and were left out:"
+            );
+            for name in &unwritten {
+                println!("  {}", name);
+            }
+        }
+        println!("Saved kmap for mode '{}' to: {:?}", mode, output);
+        Ok(())
+    }
+}
+
+impl ChordMap {
+    /// Convert the chords into kmap sections, using the given layout for each
+    /// chord. Chords that were read from a kmap file keep their original
+    /// sections and order. Any other chords, like generated ones, are added
+    /// at the end, sorted by name. Anagram numbers can't be written in kmap
+    /// files, so chords with one are an error.
+    pub fn to_kmap_file(&self, layout: KmapLayout) -> Result<KmapFile, Error> {
+        let mut from_kmaps: BTreeMap<_, KmapSection> = BTreeMap::new();
+        let mut generated = KmapSection::default();
+        for (name, chord) in self.iter() {
+            if !chord.anagram_num.is_default() {
+                return Err(Error::BadValueErr {
+                    thing: "anagram number".to_owned(),
+                    value: chord.anagram_num.to_string(),
+                })
+                .with_context(|| {
+                    format!("Can't write chord to kmap file: {}", name)
+                });
+            }
+            let section = match self.span(name) {
+                Some(span) => {
+                    from_kmaps.entry((&span.kmap, span.line)).or_default()
+                }
+                None => &mut generated,
+            };
+            section.names.push(name.to_owned());
+            section.chords.push(chord.to_owned());
+        }
+
+        let mut items: Vec<_> = from_kmaps
+            .into_values()
+            .map(|section| section.sorted_by_span(self))
+            .map(KmapItem::Section)
+            .collect();
+        items.extend(
+            generated
+                .split_to_width(layout.width())
+                .into_iter()
+                .map(KmapItem::Section),
+        );
+        Ok(KmapFile { layout, items })
+    }
+}
+
+impl KmapFile {
+    /// Save the kmap file, formatted but not sorted.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        write_kmap(path, &self.format(false))
+    }
+
+    /// Render the kmap file with every block in a section padded to the same
+    /// width, and a single blank line between sections and comments. If `sort`
+    /// is true, the blocks in each section are sorted by name.
+    pub fn format(&self, sort: bool) -> String {
+        let paragraphs: Vec<_> = self
+            .items
+            .iter()
+            .map(|item| match item {
+                KmapItem::Comment(lines) => lines.join("\n"),
+                KmapItem::Section(section) => {
+                    if sort {
+                        format_section(&section.sorted(), &self.layout)
+                    } else {
+                        format_section(section, &self.layout)
+                    }
+                }
+            })
+            .collect();
+        let mut text = paragraphs.join("\n\n");
+        text.push('\n');
+        text
+    }
+}
+
+impl KmapSection {
+    fn sorted_by_span(self, chord_map: &ChordMap) -> Self {
+        let mut blocks: Vec<_> =
+            self.names.into_iter().zip(self.chords).collect();
+        blocks.sort_by_key(|(name, _)| chord_map.span(name).cloned());
+        let (names, chords) = blocks.into_iter().unzip();
+        KmapSection { names, chords }
+    }
+
+    /// Split the section into several, so that each line fits within the
+    /// maximum line width.
+    fn split_to_width(self, layout_width: usize) -> Vec<Self> {
+        let longest_name = self
+            .names
+            .iter()
+            .map(|name| name.0.len())
+            .max()
+            .unwrap_or(0);
+        let block_width = longest_name.max(layout_width) + BLOCK_SPACING;
+        let blocks_per_section = (MAX_LINE_WIDTH / block_width).max(1);
+        self.names
+            .chunks(blocks_per_section)
+            .zip(self.chords.chunks(blocks_per_section))
+            .map(|(names, chords)| KmapSection {
+                names: names.to_vec(),
+                chords: chords.to_vec(),
+            })
+            .collect()
+    }
+
+    /// Sort the blocks by name, with any blank mappings at the end.
+    fn sorted(&self) -> Self {
+        let mut blocks: Vec<_> = self
+            .names
+            .iter()
+            .cloned()
+            .zip(self.chords.iter().cloned())
+            .collect();
+        blocks.sort_by(|(a, _), (b, _)| {
+            let a_blank = a.0 == KmapFile::BLANK_MAPPING;
+            let b_blank = b.0 == KmapFile::BLANK_MAPPING;
+            (a_blank, a).cmp(&(b_blank, b))
+        });
+        let (names, chords) = blocks.into_iter().unzip();
+        KmapSection { names, chords }
+    }
+}
+
+fn write_kmap(path: &Path, text: &str) -> Result<(), Error> {
+    fs::write(path, text)
+        .with_context(|| format!("Failed to write kmap: {:?}", path))
+}
+
+fn format_section(section: &KmapSection, layout: &KmapLayout) -> String {
+    let longest_name = section
+        .names
+        .iter()
+        .map(|name| name.0.len())
+        .max()
+        .unwrap_or(0);
+    let width = longest_name.max(layout.width()) + BLOCK_SPACING;
+
+    let mut lines = vec![pad_blocks(
+        section.names.iter().map(|name| name.0.to_owned()),
+        width,
+    )];
+    let chord_lines: Vec<_> = section
+        .chords
+        .iter()
+        .map(|chord| format_chord(chord, layout))
+        .collect();
+    for i in 0..layout.0.len() {
+        lines.push(pad_blocks(
+            chord_lines.iter().map(|chord| chord[i].to_owned()),
+            width,
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Join the blocks of a single line, padding each one to the same width.
+fn pad_blocks<I>(blocks: I, width: usize) -> String
+where
+    I: Iterator<Item = String>,
+{
+    let line: String = blocks
+        .map(|block| format!("{:width$}", block, width = width))
+        .collect();
+    line.trim_end().to_owned()
+}
+
+/// Fill in the switches of the layout with the chord, one line at a time.
+fn format_chord(chord: &Chord<KmapOrder>, layout: &KmapLayout) -> Vec<String> {
+    let mut switches = chord.iter();
+    layout
+        .0
+        .iter()
+        .map(|line| {
+            line.chars()
+                .map(|c| {
+                    if c != KmapFile::UNPRESSED_CHAR {
+                        c
+                    } else if switches.next().unwrap_or(false) {
+                        KmapFile::PRESSED_CHAR
+                    } else {
+                        KmapFile::UNPRESSED_CHAR
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
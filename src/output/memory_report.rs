@@ -0,0 +1,366 @@
+//! Estimate how much memory the generated lookup tables will take up in the
+//! firmware, without waiting for the Arduino build. The sizes are computed from
+//! the rendered CTree, so they match what's actually saved.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::mem;
+
+use error::{Error, ResultExt};
+use output::parse_ctree::{
+    parse_bytes_constructor, parse_struct_initializer, Symbols,
+};
+use types::{
+    AllData, CCode, CTree, Field, KmapPath, SeqType, SequenceEncoder,
+    SequenceEncoding,
+};
+
+/// Without compression, each keypress would need a byte for the key and a byte
+/// for the modifiers.
+const RAW_KEYPRESS_BITS: usize = 16;
+
+/// The types of the fields of each struct in `config_types.h`, in order. A
+/// type of `*` means any pointer. Trailing fields that aren't rendered, like
+/// `is_fragment` without the `Fragments` encoding, aren't counted.
+const STRUCT_FIELDS: [(&str, &[&str]); 6] = [
+    (
+        "LookupKmapTypeLenAnagram",
+        &["uint16_t", "uint16_t", "*", "*"],
+    ),
+    ("LookupKmapType", &["uint32_t", "*"]),
+    ("KmapStruct", &["*"]),
+    ("ModeStruct", &["bool", "uint8_t", "*", "*", "ChordData"]),
+    (
+        "HuffmanChar",
+        &["HuffmanBits", "uint8_t", "uint8_t", "bool", "bool"],
+    ),
+    ("Fragment", &["uint16_t", "uint16_t"]),
+];
+
+/// Estimated sizes in bytes. Struct fields are padded to the alignment the
+/// board needs, assuming the compiler lays them out in the usual way.
+#[derive(Debug, Default)]
+pub struct MemoryReport {
+    pub kmaps: Vec<(KmapPath, usize)>,
+    pub seq_types: BTreeMap<SeqType, usize>,
+    /// The lookups for each sequence length and anagram number, named like
+    /// their lookup structs (eg. `kmap0_Word_len12_anagram0`).
+    pub lookup_groups: BTreeMap<String, usize>,
+    pub huffman_table: usize,
+    pub fragment_table: usize,
+    /// The total length in bits of each seq type's sequences, as (raw,
+    /// encoded).
+    pub compression: BTreeMap<SeqType, (usize, usize)>,
+    /// All the arrays and structs, including the ones listed above.
+    pub total: usize,
+}
+
+impl AllData {
+    pub fn memory_report(&self) -> Result<MemoryReport, Error> {
+        self.memory_report_for(&self.render_main(false)?)
+    }
+
+    /// Return an error if the rendered config is larger than the
+    /// `memory_budget` setting.
+    pub(super) fn check_memory_budget(
+        &self,
+        tree: &CTree,
+    ) -> Result<(), Error> {
+        let budget = match self.user_options.memory_budget {
+            Some(budget) => budget,
+            None => return Ok(()),
+        };
+        let total = self.memory_report_for(tree)?.total;
+        if total > budget {
+            return Err(Error::OutOfRangeErr {
+                name: "estimated size of the lookup tables (bytes)".into(),
+                value: total,
+                min: 0,
+                max: budget,
+            })
+            .context(
+                "The configuration is larger than 'memory_budget'. Use \
+                 --memory-report to see which parts are largest",
+            );
+        }
+        Ok(())
+    }
+
+    fn memory_report_for(&self, tree: &CTree) -> Result<MemoryReport, Error> {
+        let mut report = MemoryReport::default();
+        let symbols = Symbols::new(tree);
+        let board = self.user_options.board_name.info();
+        let estimator = SizeEstimator {
+            pointer_bytes: board.pointer_bytes,
+            max_alignment: board.max_alignment,
+            chord_bytes: self
+                .chord_spec
+                .to_firmware(&self.chord_spec.new_chord())?
+                .to_bytes()
+                .len(),
+        };
+        let mut sizes = BTreeMap::new();
+        for (name, symbol) in symbols.iter() {
+            let size = estimator.symbol_size(symbol).with_context(|| {
+                format!("Failed to estimate size of '{}'", name)
+            })?;
+            sizes.insert(name, size);
+        }
+        report.total = sizes.values().sum();
+        report.huffman_table =
+            sizes.get("huffman_lookup").cloned().unwrap_or(0);
+        report.fragment_table = ["fragment_bits", "fragment_lookup"]
+            .iter()
+            .filter_map(|name| sizes.get(name))
+            .sum();
+        report.compression = self.compression()?;
+
+        for (i, (kmap, _)) in self.chords.iter().enumerate() {
+            let nickname = AllData::kmap_nickname(i);
+            let kmap_prefix = format!("{}_", nickname);
+            report
+                .kmaps
+                .push((kmap.to_owned(), sum_with_prefix(&sizes, &kmap_prefix)));
+
+            for &seq_type in self.sequences.seq_types() {
+                let prefix = format!("{}_{}_", nickname, seq_type);
+                *report.seq_types.entry(seq_type).or_insert(0) +=
+                    sum_with_prefix(&sizes, &prefix);
+
+                let group_prefix = format!("{}len", prefix);
+                for (name, size) in &sizes {
+                    if !name.starts_with(&group_prefix) {
+                        continue;
+                    }
+                    let group = name
+                        .trim_end_matches("_chords")
+                        .trim_end_matches("_seqs");
+                    *report
+                        .lookup_groups
+                        .entry(group.to_owned())
+                        .or_insert(0) += size;
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    fn compression(&self) -> Result<BTreeMap<SeqType, (usize, usize)>, Error> {
+        let mut compression = BTreeMap::new();
+        for &seq_type in self.sequences.seq_types() {
+            let (mut raw, mut encoded) = (0, 0);
+            let seq_map = self.sequences.get_seq_map(seq_type)?;
+            for name in seq_map.names() {
+                let seq = self.sequences.get(name, seq_type)?;
+                raw += seq.keypresses().count() * RAW_KEYPRESS_BITS;
+                encoded += seq.formatted_length_in_bits(&self.encoder)?;
+            }
+            compression.insert(seq_type, (raw, encoded));
+        }
+        Ok(compression)
+    }
+}
+
+/// The estimated total size of the lookup tables with each sequence encoding.
+#[derive(Debug)]
+pub struct EncodingComparison {
+    pub current: SequenceEncoding,
+    pub totals: Vec<(SequenceEncoding, usize)>,
+}
+
+impl AllData {
+    /// Estimate the size of the lookup tables with every sequence encoding,
+    /// to see which one is best for these settings.
+    pub fn compare_encodings(&mut self) -> Result<EncodingComparison, Error> {
+        let frequencies = self.user_options.load_huffman_corpus()?;
+        let mut totals = Vec::new();
+        for &encoding in &SequenceEncoding::ALL {
+            let encoder = SequenceEncoder::new(
+                encoding,
+                &self.sequences,
+                &self.dictionary,
+                frequencies.as_ref(),
+            )?;
+            let original = mem::replace(&mut self.encoder, encoder);
+            let report = self.memory_report();
+            self.encoder = original;
+            let report = report.with_context(|| {
+                format!("Failed to estimate size with encoding {:?}", encoding)
+            })?;
+            totals.push((encoding, report.total));
+        }
+        Ok(EncodingComparison {
+            current: self.user_options.sequence_encoding,
+            totals,
+        })
+    }
+}
+
+impl EncodingComparison {
+    pub fn smallest(&self) -> SequenceEncoding {
+        self.totals
+            .iter()
+            .min_by_key(|(_, total)| total)
+            .map(|(encoding, _)| *encoding)
+            .unwrap_or(self.current)
+    }
+}
+
+fn sum_with_prefix(sizes: &BTreeMap<&str, usize>, prefix: &str) -> usize {
+    sizes
+        .iter()
+        .filter(|(name, _)| name.starts_with(prefix))
+        .map(|(_, size)| size)
+        .sum()
+}
+
+/// Sizes of the rendered C values.
+struct SizeEstimator {
+    pointer_bytes: usize,
+    max_alignment: usize,
+    /// Struct fields can refer to chords by name, so the chord size is needed
+    /// separately.
+    chord_bytes: usize,
+}
+
+impl SizeEstimator {
+    fn symbol_size(&self, symbol: &CTree) -> Result<usize, Error> {
+        match symbol {
+            CTree::Array { values, c_type, .. }
+            | CTree::StdArray { values, c_type, .. } => values
+                .iter()
+                .map(|value| self.value_size(c_type, value))
+                .sum(),
+            CTree::StructInstance { fields, c_type, .. } => {
+                self.fields_size(c_type, fields)
+            }
+            _ => Ok(0),
+        }
+    }
+
+    fn value_size(
+        &self,
+        c_type: &CCode,
+        value: &CCode,
+    ) -> Result<usize, Error> {
+        let c_type = c_type.0.trim_start_matches("const ").trim();
+        if c_type.ends_with('*') {
+            return Ok(self.pointer_bytes);
+        }
+        Ok(match c_type {
+            "uint8_t" | "bool" => 1,
+            "uint16_t" => 2,
+            "uint32_t" => 4,
+            "ChordData" if !value.0.starts_with(c_type) => self.chord_bytes,
+            "ChordData" | "HuffmanBits" => {
+                parse_bytes_constructor(value, &CCode(c_type.to_owned()))?.len()
+            }
+            _ if struct_fields(c_type).is_some() => self.fields_size(
+                &CCode(c_type.to_owned()),
+                &parse_struct_initializer(value)?,
+            )?,
+            // All the generated enums use `uint8_t` as their underlying type.
+            _ => 1,
+        })
+    }
+
+    fn fields_size(
+        &self,
+        c_type: &CCode,
+        fields: &[Field],
+    ) -> Result<usize, Error> {
+        let field_types =
+            struct_fields(&c_type.0).ok_or_else(|| Error::LookupErr {
+                key: c_type.to_string(),
+                container: "known struct types".into(),
+            })?;
+        let mut size: usize = 0;
+        for (field, field_type) in fields.iter().zip(field_types) {
+            size = size.next_multiple_of(self.alignment(field_type));
+            size +=
+                self.value_size(&CCode(field_type.to_string()), &field.value)?;
+        }
+        // Pad the end too, so every struct in an array is aligned.
+        Ok(size.next_multiple_of(self.alignment(&c_type.0)))
+    }
+
+    /// The alignment of a field type in bytes.
+    fn alignment(&self, c_type: &str) -> usize {
+        let natural = if c_type.ends_with('*') {
+            self.pointer_bytes
+        } else if let Some(fields) = struct_fields(c_type) {
+            fields
+                .iter()
+                .map(|field| self.alignment(field))
+                .max()
+                .unwrap_or(1)
+        } else {
+            match c_type {
+                "uint16_t" => 2,
+                "uint32_t" => 4,
+                // Chords and Huffman codes are arrays of bytes, and enums
+                // are `uint8_t`.
+                _ => 1,
+            }
+        };
+        natural.min(self.max_alignment)
+    }
+}
+
+fn struct_fields(c_type: &str) -> Option<&'static [&'static str]> {
+    STRUCT_FIELDS
+        .iter()
+        .find(|(name, _)| *name == c_type)
+        .map(|(_, fields)| *fields)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Estimated memory use of the lookup tables (bytes):")?;
+        writeln!(f, "  Huffman table: {}", self.huffman_table)?;
+        writeln!(f, "  Fragment table: {}", self.fragment_table)?;
+        writeln!(f, "  Kmaps:")?;
+        for (kmap, size) in &self.kmaps {
+            writeln!(f, "    {}: {}", kmap, size)?;
+        }
+        writeln!(f, "  Sequence types:")?;
+        for (seq_type, size) in &self.seq_types {
+            writeln!(f, "    {}: {}", seq_type, size)?;
+        }
+        writeln!(f, "  Lookup groups:")?;
+        for (group, size) in &self.lookup_groups {
+            writeln!(f, "    {}: {}", group, size)?;
+        }
+        writeln!(f, "  Total: {}", self.total)?;
+        writeln!(f, "Sequence compression (bits, raw -> encoded):")?;
+        for (seq_type, (raw, encoded)) in &self.compression {
+            let percent = if *raw == 0 { 100 } else { encoded * 100 / raw };
+            writeln!(
+                f,
+                "  {}: {} -> {} ({}%)",
+                seq_type, raw, encoded, percent
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for EncodingComparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Estimated memory use with each sequence encoding (bytes):"
+        )?;
+        for (encoding, total) in &self.totals {
+            let current = if *encoding == self.current {
+                " (current)"
+            } else {
+                ""
+            };
+            writeln!(f, "  {:?}: {}{}", encoding, total, current)?;
+        }
+        writeln!(f, "Smallest: {:?}", self.smallest())
+    }
+}
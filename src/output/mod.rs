@@ -4,14 +4,20 @@
 #[macro_use]
 mod c_struct;
 
+mod export;
 mod format_ctree;
+mod format_kmap;
+mod memory_report;
+mod parse_ctree;
 mod render_all_data;
 mod render_chord;
+mod render_encoder;
 mod render_huffman;
 mod render_kmap;
 mod render_mode;
 mod render_sequence;
 mod render_user_options;
+mod verify_lookups;
 
 use self::render_kmap::KmapBuilder;
 use self::render_mode::ModeBuilder;
@@ -0,0 +1,159 @@
+//! Read values back out of rendered CTrees. This is the reverse of some of the
+//! formatting in `format_ctree.rs`, and is used to check that the generated
+//! firmware config decodes to what we meant to encode.
+
+use bit_vec::BitVec;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use error::Error;
+use types::{CCode, CTree, Field};
+
+/// All the named arrays and struct instances in a CTree.
+pub struct Symbols<'a>(HashMap<&'a str, &'a CTree>);
+
+////////////////////////////////////////////////////////////////////////////////
+
+impl<'a> Symbols<'a> {
+    pub fn new(tree: &'a CTree) -> Self {
+        let mut symbols = Symbols(HashMap::new());
+        symbols.collect(tree);
+        symbols
+    }
+
+    fn collect(&mut self, tree: &'a CTree) {
+        match tree {
+            CTree::Array { name, .. }
+            | CTree::StdArray { name, .. }
+            | CTree::StructInstance { name, .. } => {
+                self.0.insert(&name.0, tree);
+            }
+            CTree::Group(trees) => {
+                for tree in trees {
+                    self.collect(tree);
+                }
+            }
+            CTree::Namespace { contents, .. }
+            | CTree::Ifndef { contents, .. } => self.collect(contents),
+            _ => (),
+        }
+    }
+
+    /// Iterate over the names of the symbols, along with their definitions.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, &'a CTree)> + '_ {
+        self.0.iter().map(|(&name, &tree)| (name, tree))
+    }
+
+    /// Get the values of the array with the given name.
+    pub fn array(&self, name: &str) -> Result<&'a [CCode], Error> {
+        match self.get(name)? {
+            CTree::Array { values, .. } | CTree::StdArray { values, .. } => {
+                Ok(values)
+            }
+            _ => Err(wrong_kind(name, "array")),
+        }
+    }
+
+    /// Get the fields of the struct instance with the given name.
+    pub fn fields(&self, name: &str) -> Result<&'a [Field], Error> {
+        match self.get(name)? {
+            CTree::StructInstance { fields, .. } => Ok(fields),
+            _ => Err(wrong_kind(name, "struct instance")),
+        }
+    }
+
+    fn get(&self, name: &str) -> Result<&'a CTree, Error> {
+        self.0.get(name).cloned().ok_or_else(|| Error::LookupErr {
+            key: name.to_owned(),
+            container: "rendered firmware config".into(),
+        })
+    }
+}
+
+/// Get the value of the struct field with the given name.
+pub fn field<'a>(fields: &'a [Field], name: &str) -> Result<&'a CCode, Error> {
+    fields
+        .iter()
+        .find(|field| field.name.0 == name)
+        .map(|field| &field.value)
+        .ok_or_else(|| Error::Missing {
+            missing: format!("field '{}'", name),
+            container: "rendered struct".into(),
+        })
+}
+
+/// Get the name of the variable that an address like `&name` points to.
+pub fn dereference(address: &CCode) -> Result<&str, Error> {
+    if address.0.starts_with('&') {
+        Ok(&address.0[1..])
+    } else {
+        Err(bad_value("address", address))
+    }
+}
+
+pub fn parse_number<T>(code: &CCode) -> Result<T, Error>
+where
+    T: FromStr,
+{
+    code.0.trim().parse().map_err(|_| bad_value("number", code))
+}
+
+/// Parse a constructor of the form `CType({1, 2, 3})` into its bytes.
+pub fn parse_bytes_constructor(
+    code: &CCode,
+    c_type: &CCode,
+) -> Result<Vec<u8>, Error> {
+    let prefix = format!("{}({{", c_type);
+    if !code.0.starts_with(&prefix) || !code.0.ends_with("})") {
+        return Err(bad_value(&format!("{} constructor", c_type), code));
+    }
+    code.0[prefix.len()..code.0.len() - 2]
+        .split(',')
+        .map(|byte| parse_number(&CCode(byte.to_owned())))
+        .collect()
+}
+
+/// Rebuild bits from the blocks of a `BitVec<u8>`, which store each bit at
+/// its index modulo 8, so they can't be read back with `BitVec::from_bytes()`.
+pub fn bits_from_blocks(blocks: &[u8]) -> BitVec<u8> {
+    blocks
+        .iter()
+        .flat_map(|block| (0..8).map(move |i| block & (1 << i) != 0))
+        .collect()
+}
+
+/// Parse the lines of a struct initializer, like the ones made by
+/// `CTree::initializer()`, back into fields.
+pub fn parse_struct_initializer(code: &CCode) -> Result<Vec<Field>, Error> {
+    let lines: Vec<&str> = code.0.lines().collect();
+    if lines.len() < 2 || lines[0] != "{" || lines[lines.len() - 1] != "}" {
+        return Err(bad_value("struct initializer", code));
+    }
+    lines[1..lines.len() - 1]
+        .iter()
+        .map(|line| {
+            let mut parts = line.trim().rsplitn(2, ", // ");
+            match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) => Ok(Field {
+                    name: CCode(name.to_owned()),
+                    value: CCode(value.to_owned()),
+                }),
+                _ => Err(bad_value("struct initializer line", code)),
+            }
+        })
+        .collect()
+}
+
+fn wrong_kind(name: &str, kind: &str) -> Error {
+    Error::BadValueErr {
+        thing: kind.to_owned(),
+        value: name.to_owned(),
+    }
+}
+
+fn bad_value(thing: &str, code: &CCode) -> Error {
+    Error::BadValueErr {
+        thing: thing.to_owned(),
+        value: code.to_string(),
+    }
+}
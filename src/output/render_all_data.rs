@@ -1,8 +1,9 @@
 use std::collections::BTreeMap;
+use std::path::PathBuf;
 
 use time::*;
 
-use error::Error;
+use error::{Error, ResultExt};
 use types::{
     AllData, CCode, CEnumVariant, CTree, Command, KeyDefs, KmapPath, ModeName,
     Modifier, Name, SeqType, ToC,
@@ -16,7 +17,17 @@ impl AllData {
     /// configuration. `file_name_base` should have no extension. `.h` and
     /// `.cpp` will be added to it as needed.
     pub fn save_as(&self, file_name_base: &str) -> Result<(), Error> {
-        self.save_helper(file_name_base, true)
+        self.save_helper(&self.output_directory, file_name_base, true)
+    }
+
+    /// Like `save_as()`, but save in the given directory instead of the output
+    /// directory from the settings.
+    pub fn save_in(
+        &self,
+        directory: &PathBuf,
+        file_name_base: &str,
+    ) -> Result<(), Error> {
+        self.save_helper(directory, file_name_base, true)
     }
 
     /// Used for testing. The message contains a timestamp that would make the
@@ -27,28 +38,29 @@ impl AllData {
         &self,
         file_name_base: &str,
     ) -> Result<(), Error> {
-        self.save_helper(file_name_base, false)
+        self.save_helper(&self.output_directory, file_name_base, false)
     }
 
     fn save_helper(
         &self,
+        directory: &PathBuf,
         file_name_base: &str,
         with_message: bool,
     ) -> Result<(), Error> {
-        let main_files =
-            self.render_main(with_message)?.format(file_name_base)?;
+        let main_tree = self.render_main(with_message)?;
+        self.verify_lookups(&main_tree)
+            .context("Failed to verify the generated lookup tables")?;
+        self.check_memory_budget(&main_tree)?;
+        let main_files = main_tree.format(file_name_base)?;
 
         let early_name_base = format!("{}_early", file_name_base);
         let early_files = self
             .render_early_config(with_message)?
             .format(&early_name_base)?;
 
-        let mut file_names =
-            main_files.save(&self.output_directory, file_name_base)?;
+        let mut file_names = main_files.save(directory, file_name_base)?;
 
-        file_names.extend(
-            early_files.save(&self.output_directory, &early_name_base)?,
-        );
+        file_names.extend(early_files.save(directory, &early_name_base)?);
 
         let file_name_list = file_names
             .into_iter()
@@ -72,7 +84,7 @@ impl AllData {
         let mut namespace = Vec::new();
         namespace.extend(self.user_options.render_early());
         namespace.push(KmapBuilder::render_limits());
-        namespace.push(self.huffman_table.render_early());
+        namespace.push(self.encoder.render_early());
         namespace.push(ModeName::render_c_enum(self.modes.keys()));
 
         // Use the first mode enum variant as the default mode
@@ -104,14 +116,17 @@ impl AllData {
         Ok(CTree::Group(group))
     }
 
-    fn render_main(&self, with_message: bool) -> Result<CTree, Error> {
+    pub(crate) fn render_main(
+        &self,
+        with_message: bool,
+    ) -> Result<CTree, Error> {
         Ok(CTree::Group(vec![
             intro(with_message)?,
             CTree::Namespace {
                 name: "conf".to_c(),
                 contents: Box::new(CTree::Group(vec![
                     self.user_options.render(),
-                    self.huffman_table.render()?,
+                    self.encoder.render()?,
                     self.render_modifiers()?,
                     Command::render_c_enum(self.commands.iter()),
                     self.render_modes()?,
@@ -166,10 +181,10 @@ impl AllData {
         let mut g = Vec::new();
         for (i, (kmap_name, chords)) in self.chords.iter().enumerate() {
             let builder = KmapBuilder {
-                kmap_nickname: format!("kmap{}", i),
+                kmap_nickname: AllData::kmap_nickname(i),
                 chord_map: chords,
                 seq_maps: &self.sequences,
-                huffman_table: &self.huffman_table,
+                encoder: &self.encoder,
                 chord_spec: self.chord_spec.clone(),
             };
             let (tree, kmap_struct_name) = builder.render()?;
@@ -179,6 +194,12 @@ impl AllData {
         Ok((CTree::Group(g), kmap_struct_names))
     }
 
+    /// The prefix of the names of everything rendered for the kmap at the
+    /// given index in `self.chords`.
+    pub(super) fn kmap_nickname(index: usize) -> String {
+        format!("kmap{}", index)
+    }
+
     fn render_modifiers(&self) -> Result<CTree, Error> {
         fn to_variants(mod_names: &[Name]) -> Vec<CCode> {
             mod_names
@@ -1,5 +1,7 @@
 use std::cmp::Ordering;
 
+use error::Error;
+use output::parse_ctree::parse_bytes_constructor;
 use types::{CCode, Chord, FirmwareOrder, ToC};
 
 impl Ord for Chord<FirmwareOrder> {
@@ -27,4 +29,15 @@ impl Chord<FirmwareOrder> {
     pub fn c_type_name() -> CCode {
         "ChordData".to_c()
     }
+
+    /// The bytes that `to_c_constructor()` would render.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.switches().blocks().collect()
+    }
+
+    /// Parse the bytes back out of a constructor made by
+    /// `to_c_constructor()`.
+    pub fn parse_c_constructor(code: &CCode) -> Result<Vec<u8>, Error> {
+        parse_bytes_constructor(code, &Self::c_type_name())
+    }
 }
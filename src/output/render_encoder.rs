@@ -0,0 +1,122 @@
+use bit_vec::BitVec;
+
+use error::{Error, ResultExt};
+use output::parse_ctree::{
+    bits_from_blocks, field, parse_number, parse_struct_initializer, Symbols,
+};
+use types::{
+    CCode, CTree, Field, HuffmanTable, Sequence, SequenceEncoder,
+    SequenceEncoding, ToC,
+};
+use util::ensure_u16;
+
+c_struct!(
+    struct Fragment {
+        start_bit: usize,
+        num_bits: usize,
+    }
+);
+
+impl SequenceEncoder {
+    pub fn render(&self) -> Result<CTree, Error> {
+        let mut bits: BitVec<u8> = BitVec::default();
+        let mut fragments = Vec::new();
+        for fragment in &self.fragments {
+            let fragment_bits =
+                fragment.as_huffman_bits(&self.huffman_table)?;
+            fragments.push(
+                Fragment {
+                    start_bit: bits.len(),
+                    num_bits: fragment_bits.len(),
+                }
+                .initializer(),
+            );
+            bits.extend(fragment_bits);
+        }
+        ensure_u16(bits.len()).context("Fragment table is too long")?;
+
+        let with_fragments = self.encoding == SequenceEncoding::Fragments;
+        let mut group = vec![self.huffman_table.render(with_fragments)?];
+        if with_fragments {
+            // These are std::arrays, since there might not be any fragments.
+            group.push(CTree::StdArray {
+                name: "fragment_bits".to_c(),
+                values: bits.blocks().map(|x| x.to_c()).collect(),
+                c_type: "uint8_t".to_c(),
+                is_extern: true,
+            });
+            group.push(CTree::StdArray {
+                name: "fragment_lookup".to_c(),
+                values: fragments,
+                c_type: Fragment::c_type(),
+                is_extern: true,
+            });
+        }
+        Ok(CTree::Group(group))
+    }
+
+    pub fn render_early(&self) -> CTree {
+        CTree::Group(vec![
+            self.huffman_table.render_early(),
+            CTree::DefineIf {
+                name: "USE_FRAGMENTS".to_c(),
+                is_defined: self.encoding == SequenceEncoding::Fragments,
+            },
+        ])
+    }
+
+    /// Reconstruct the encoder from a rendered config. This is the reverse of
+    /// `render()`.
+    pub fn from_symbols(symbols: &Symbols) -> Result<Self, Error> {
+        let huffman_table =
+            HuffmanTable::from_initializers(symbols.array("huffman_lookup")?)
+                .context("Failed to decode huffman table")?;
+        // The fragment tables are only rendered for the `Fragments`
+        // encoding.
+        let (encoding, fragments) = match symbols.array("fragment_lookup") {
+            Ok(lookup) => (
+                SequenceEncoding::Fragments,
+                Self::fragments_from_symbols(symbols, lookup, &huffman_table)
+                    .context("Failed to decode fragment table")?,
+            ),
+            Err(_) => (SequenceEncoding::Huffman, Vec::new()),
+        };
+
+        Ok(SequenceEncoder {
+            encoding,
+            huffman_table,
+            fragments,
+        })
+    }
+
+    fn fragments_from_symbols(
+        symbols: &Symbols,
+        lookup: &[CCode],
+        huffman_table: &HuffmanTable,
+    ) -> Result<Vec<Sequence>, Error> {
+        let bytes = symbols
+            .array("fragment_bits")?
+            .iter()
+            .map(parse_number)
+            .collect::<Result<Vec<u8>, Error>>()?;
+        let bits = bits_from_blocks(&bytes);
+
+        lookup
+            .iter()
+            .map(|initializer| {
+                let fields = parse_struct_initializer(initializer)?;
+                let start: usize = parse_number(field(&fields, "start_bit")?)?;
+                let length: usize = parse_number(field(&fields, "num_bits")?)?;
+                let fragment_bits: BitVec<u8> =
+                    bits.iter().skip(start).take(length).collect();
+                // Fragments can't contain other fragments.
+                huffman_table.decode(&fragment_bits, |code| {
+                    Err(Error::BadValueErr {
+                        thing: "code in fragment".into(),
+                        value: code.to_string(),
+                    })
+                })
+            })
+            .collect()
+    }
+}
@@ -1,7 +1,14 @@
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 
 use error::Error;
-use types::{CCode, CTree, Field, HuffmanEntry, HuffmanTable, KeyPress, ToC};
+use output::parse_ctree::{
+    bits_from_blocks, field, parse_bytes_constructor, parse_number,
+    parse_struct_initializer,
+};
+use types::{
+    CCode, CTree, Field, HuffmanEntry, HuffmanTable, KeyPress, SymbolKind, ToC,
+};
 
 c_struct!(
     struct HuffmanChar {
@@ -13,7 +20,10 @@ c_struct!(
 );
 
 impl HuffmanTable {
-    pub fn render(&self) -> Result<CTree, Error> {
+    /// Render the lookup table. The `is_fragment` field is only included
+    /// if the table can contain fragments, so that it doesn't take up space
+    /// otherwise.
+    pub fn render(&self, with_fragments: bool) -> Result<CTree, Error> {
         let mut group = Vec::new();
         group.push(CTree::ConstVar {
             name: "MIN_HUFFMAN_CODE_BIT_LEN".to_c(),
@@ -24,7 +34,7 @@ impl HuffmanTable {
 
         group.push(CTree::Array {
             name: "huffman_lookup".to_c(),
-            values: self.initializers(),
+            values: self.initializers(with_fragments),
             c_type: HuffmanChar::c_type(),
             is_extern: true,
         });
@@ -39,15 +49,50 @@ impl HuffmanTable {
         }
     }
 
-    fn initializers(&self) -> Vec<CCode> {
+    /// Reconstruct a table from the initializers of a rendered
+    /// `huffman_lookup` array. This is the reverse of `initializers()`.
+    pub fn from_initializers(initializers: &[CCode]) -> Result<Self, Error> {
+        let mut map = BTreeMap::new();
+        for initializer in initializers {
+            let fields = parse_struct_initializer(initializer)?;
+            let mut bits = bits_from_blocks(&parse_bytes_constructor(
+                field(&fields, "bits")?,
+                &HuffmanEntry::c_type_name(),
+            )?);
+            bits.truncate(parse_number(field(&fields, "num_bits")?)?);
+            let key = KeyPress::untruncate(field(&fields, "key_code")?)?;
+            let kind = if *field(&fields, "is_mod")? == true.to_c() {
+                SymbolKind::Mod
+            } else if field(&fields, "is_fragment").ok() == Some(&true.to_c()) {
+                SymbolKind::Fragment
+            } else {
+                SymbolKind::Key
+            };
+            map.insert(key, HuffmanEntry::new(bits, kind));
+        }
+        Ok(HuffmanTable(map))
+    }
+
+    fn initializers(&self, with_fragments: bool) -> Vec<CCode> {
         let mut v: Vec<_> = self
             .0
             .iter()
-            .map(|(key, entry)| entry.to_huffman_char(key))
+            .map(|(key, entry)| (entry.to_huffman_char(key), entry.kind))
             .collect();
-        v.sort();
+        v.sort_by(|a, b| a.0.cmp(&b.0));
         v.into_iter()
-            .map(|huffchar| huffchar.initializer())
+            .map(|(huffchar, kind)| {
+                let mut tree = huffchar.render(CCode::new());
+                if with_fragments {
+                    if let CTree::StructInstance { ref mut fields, .. } = tree {
+                        fields.push(Field {
+                            name: "is_fragment".to_c(),
+                            value: (kind == SymbolKind::Fragment).to_c(),
+                        });
+                    }
+                }
+                tree.initializer()
+            })
             .collect()
     }
 }
@@ -58,7 +103,7 @@ impl HuffmanEntry {
             bits: self.to_c_constructor(),
             num_bits: self.num_bits(),
             key_code: KeyPress::truncate(key),
-            is_mod: self.is_mod,
+            is_mod: self.kind == SymbolKind::Mod,
         }
     }
 
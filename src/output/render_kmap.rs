@@ -1,9 +1,13 @@
+use bit_vec::BitVec;
 use std::collections::{BTreeMap, BTreeSet};
 
 use error::{Error, ResultExt};
+use output::parse_ctree::{
+    bits_from_blocks, dereference, field, parse_number, Symbols,
+};
 use types::{
     AllSeqMaps, AnagramNum, CCode, CTree, Chord, ChordMap, ChordSpec, Field,
-    HuffmanTable, Name, SeqType, Sequence, ToC,
+    FirmwareOrder, Name, SeqType, Sequence, SequenceEncoder, ToC,
 };
 use util::usize_to_u16;
 
@@ -22,10 +26,20 @@ pub struct KmapBuilder<'a> {
     pub kmap_nickname: String,
     pub chord_map: &'a ChordMap,
     pub seq_maps: &'a AllSeqMaps,
-    pub huffman_table: &'a HuffmanTable,
+    pub encoder: &'a SequenceEncoder,
     pub chord_spec: ChordSpec,
 }
 
+/// A single chord and its sequence, as decoded from the rendered lookups.
+#[derive(Debug)]
+pub struct DecodedLookup {
+    pub seq_type: SeqType,
+    /// The bytes of the chord, in firmware order.
+    pub chord: Vec<u8>,
+    pub anagram: AnagramNum,
+    pub sequence: Sequence,
+}
+
 c_struct!(
     struct KmapStruct {
         lookups_for_kmap: CCode,
@@ -172,8 +186,7 @@ impl<'a> KmapBuilder<'a> {
                 .map(|(c, s)| (c.to_c_constructor(), s))
                 .unzip();
 
-            let seq_bytes =
-                Sequence::flatten(&seqs).as_bytes(&self.huffman_table)?;
+            let seq_bytes = Sequence::encode_all(&seqs, self.encoder)?;
 
             let chords_name = format!("{}_chords", struct_name).to_c();
             let seqs_name = format!("{}_seqs", struct_name).to_c();
@@ -221,7 +234,7 @@ impl<'a> KmapBuilder<'a> {
             let info = LengthAndAnagram::new(
                 self.seq_maps
                     .get(name, seq_type)?
-                    .formatted_length_in_bits(&self.huffman_table)?,
+                    .formatted_length_in_bits(self.encoder)?,
                 self.chord_map.get(name).unwrap().anagram_num,
             )?;
 
@@ -234,6 +247,97 @@ impl<'a> KmapBuilder<'a> {
         Ok(grouped_names)
     }
 
+    /// Decode all the lookups of a kmap struct made by `render()`. This is the
+    /// reverse of `render()`, except that the names of the chords are lost.
+    /// The lookups for each seq type must be in the order of `seq_types`,
+    /// since that's how the firmware will index them.
+    pub fn decode(
+        symbols: &Symbols,
+        kmap_struct_name: &str,
+        seq_types: &[&SeqType],
+        encoder: &SequenceEncoder,
+    ) -> Result<Vec<DecodedLookup>, Error> {
+        let kmap_struct = symbols.fields(kmap_struct_name)?;
+        let seq_type_addresses =
+            symbols.array(&field(kmap_struct, "lookups_for_kmap")?.0)?;
+        if seq_type_addresses.len() != seq_types.len() {
+            return Err(Error::BadValueErr {
+                thing: "number of sequence types".into(),
+                value: seq_type_addresses.len().to_string(),
+            });
+        }
+
+        let mut decoded = Vec::new();
+        for (address, &&seq_type) in seq_type_addresses.iter().zip(seq_types) {
+            let seq_type_struct = symbols.fields(dereference(address)?)?;
+            let addresses =
+                symbols.array(&field(seq_type_struct, "lookups")?.0)?;
+            let num_lookups: usize =
+                parse_number(field(seq_type_struct, "num_lookups")?)?;
+            if num_lookups != addresses.len() {
+                return Err(Error::BadValueErr {
+                    thing: "num_lookups".into(),
+                    value: num_lookups.to_string(),
+                });
+            }
+
+            for address in addresses {
+                let name = dereference(address)?;
+                decoded.extend(
+                    Self::decode_lookup(symbols, name, seq_type, encoder)
+                        .with_context(|| {
+                            format!("Failed to decode lookup: '{}'", name)
+                        })?,
+                );
+            }
+        }
+        Ok(decoded)
+    }
+
+    fn decode_lookup(
+        symbols: &Symbols,
+        struct_name: &str,
+        seq_type: SeqType,
+        encoder: &SequenceEncoder,
+    ) -> Result<Vec<DecodedLookup>, Error> {
+        let fields = symbols.fields(struct_name)?;
+        let info =
+            LengthAndAnagram::parse(field(fields, "seq_bit_len_and_anagram")?)?;
+        let num_chords: usize = parse_number(field(fields, "num_chords")?)?;
+        let chords = symbols.array(&field(fields, "chords")?.0)?;
+        let seq_bytes = symbols
+            .array(&field(fields, "sequences")?.0)?
+            .iter()
+            .map(parse_number)
+            .collect::<Result<Vec<u8>, Error>>()?;
+        let seq_bits = bits_from_blocks(&seq_bytes);
+
+        if chords.len() != num_chords
+            || seq_bits.len() < num_chords * info.length
+        {
+            return Err(Error::BadValueErr {
+                thing: "num_chords".into(),
+                value: num_chords.to_string(),
+            });
+        }
+
+        chords
+            .iter()
+            .enumerate()
+            .map(|(i, chord)| {
+                let start = i * info.length;
+                let bits: BitVec<u8> =
+                    seq_bits.iter().skip(start).take(info.length).collect();
+                Ok(DecodedLookup {
+                    seq_type,
+                    chord: Chord::<FirmwareOrder>::parse_c_constructor(chord)?,
+                    anagram: info.anagram,
+                    sequence: encoder.decode(&bits)?,
+                })
+            })
+            .collect()
+    }
+
     /// This depends on the representation in the firmware lookup tables.
     fn max_allowed_bit_length() -> usize {
         // We currently use 12 bits to store length
@@ -292,6 +396,29 @@ impl LengthAndAnagram {
         format!("{}({}, {})", Self::c_type(), self.length, self.anagram).to_c()
     }
 
+    /// The reverse of `initializer()`.
+    fn parse(code: &CCode) -> Result<Self, Error> {
+        let prefix = format!("{}(", Self::c_type());
+        let bad_value = || Error::BadValueErr {
+            thing: format!("{} initializer", Self::c_type()),
+            value: code.to_string(),
+        };
+        if !code.0.starts_with(&prefix) || !code.0.ends_with(')') {
+            return Err(bad_value());
+        }
+        let args: Vec<CCode> = code.0[prefix.len()..code.0.len() - 1]
+            .split(',')
+            .map(|arg| arg.to_c())
+            .collect();
+        if args.len() != 2 {
+            return Err(bad_value());
+        }
+        Self::new(
+            parse_number(&args[0])?,
+            AnagramNum::new(parse_number(&args[1])?)?,
+        )
+    }
+
     fn c_type() -> CCode {
         "LengthAndAnagram".to_c()
     }
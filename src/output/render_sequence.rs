@@ -1,7 +1,9 @@
 use bit_vec::BitVec;
 
 use error::Error;
-use types::{CCode, HuffmanTable, KeyPress, Sequence, ToC};
+use types::{
+    CCode, HuffmanTable, KeyPress, SeqToken, Sequence, SequenceEncoder, ToC,
+};
 
 impl KeyPress {
     pub fn truncate(contents: &CCode) -> CCode {
@@ -10,6 +12,19 @@ impl KeyPress {
         CCode(format!("static_cast<uint8_t>({})", contents))
     }
 
+    /// The reverse of `truncate()`.
+    pub fn untruncate(code: &CCode) -> Result<CCode, Error> {
+        let prefix = "static_cast<uint8_t>(";
+        if code.0.starts_with(prefix) && code.0.ends_with(')') {
+            Ok(code.0[prefix.len()..code.0.len() - 1].to_c())
+        } else {
+            Err(Error::BadValueErr {
+                thing: "truncated key code".into(),
+                value: code.to_string(),
+            })
+        }
+    }
+
     pub fn format_mods(&self) -> CCode {
         // TODO think about this
         if self.mods.is_empty() {
@@ -40,24 +55,53 @@ impl KeyPress {
 }
 
 impl Sequence {
-    pub fn as_bytes(&self, table: &HuffmanTable) -> Result<Vec<CCode>, Error> {
-        // TODO different name for "bytes"?
-        Ok(self
-            .as_bits(table)?
-            .blocks()
-            .map(|x: u8| x.to_c())
-            .collect())
+    /// Encode the sequences one after another, as the bytes of a C array.
+    /// Each one is encoded separately, so fragments never span two of them.
+    pub fn encode_all(
+        seqs: &[&Self],
+        encoder: &SequenceEncoder,
+    ) -> Result<Vec<CCode>, Error> {
+        let mut bits: BitVec<u8> = BitVec::default();
+        for seq in seqs {
+            bits.extend(seq.as_bits(encoder)?);
+        }
+        Ok(bits.blocks().map(|x: u8| x.to_c()).collect())
     }
 
     pub fn formatted_length_in_bits(
         &self,
-        table: &HuffmanTable,
+        encoder: &SequenceEncoder,
     ) -> Result<usize, Error> {
         // TODO don't compute twice!
-        Ok(self.as_bits(table)?.len())
+        Ok(self.as_bits(encoder)?.len())
     }
 
-    pub fn as_bits(&self, table: &HuffmanTable) -> Result<BitVec<u8>, Error> {
+    /// Encode the sequence, using the encoder's fragments wherever they fit.
+    pub fn as_bits(
+        &self,
+        encoder: &SequenceEncoder,
+    ) -> Result<BitVec<u8>, Error> {
+        let table = &encoder.huffman_table;
+        let mut bits = BitVec::default();
+        for token in encoder.tokens(self) {
+            match token {
+                SeqToken::KeyPress(keypress) => {
+                    bits.extend(keypress.huffman(table)?)
+                }
+                SeqToken::Fragment(index) => bits.extend(
+                    table.bits(&SequenceEncoder::fragment_code(index))?,
+                ),
+            }
+        }
+        Ok(bits)
+    }
+
+    /// Encode the sequence one keypress at a time, without any fragments.
+    /// This is how the fragments themselves are stored.
+    pub fn as_huffman_bits(
+        &self,
+        table: &HuffmanTable,
+    ) -> Result<BitVec<u8>, Error> {
         let mut bits = BitVec::default();
         for keypress in self.keypresses() {
             bits.extend(keypress.huffman(table)?)
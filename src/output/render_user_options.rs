@@ -97,7 +97,7 @@ impl UserOptions {
             },
             CTree::ConstVar {
                 name: "USE_STANDBY_INTERRUPTS".to_c(),
-                value: self.use_standby_interrupts.to_c(),
+                value: self.use_standby_interrupts().to_c(),
                 c_type: "bool".to_c(),
                 is_extern: true,
             },
@@ -0,0 +1,197 @@
+//! Check the generated lookup tables by decoding them back into chords and
+//! sequences, the same way the firmware will, and comparing them with the
+//! settings they were generated from.
+
+use std::collections::BTreeMap;
+
+use error::{Error, ResultExt};
+use output::parse_ctree::{dereference, field, Symbols};
+use output::KmapBuilder;
+use types::{
+    AllData, CTree, KeyPress, KmapPath, Name, SeqType, Sequence,
+    SequenceEncoder,
+};
+
+/// A seq type, the firmware bytes of a chord, and its anagram number.
+type LookupKey = (SeqType, Vec<u8>, u8);
+
+/// A lookup that should be in the config, and the name it came from.
+type ExpectedLookup<'a> = (LookupKey, (&'a Name, &'a Sequence));
+
+impl AllData {
+    /// Decode every lookup in the rendered main config tree and return an error
+    /// listing the differences if they don't match the settings.
+    pub fn verify_lookups(&self, tree: &CTree) -> Result<(), Error> {
+        let symbols = Symbols::new(tree);
+        let encoder = SequenceEncoder::from_symbols(&symbols)
+            .context("Failed to decode sequence encoder")?;
+
+        let mut differences = Vec::new();
+        let mut kmap_struct_names: BTreeMap<&str, &KmapPath> = BTreeMap::new();
+        let mode_structs = symbols.array("mode_structs")?;
+        if mode_structs.len() != self.modes.len() {
+            differences.push(format!(
+                "Expected {} modes, but found {}",
+                self.modes.len(),
+                mode_structs.len()
+            ));
+        }
+
+        for (address, (mode, info)) in mode_structs.iter().zip(&self.modes) {
+            let mode_struct = symbols.fields(dereference(address)?)?;
+            let kmaps = symbols.array(&field(mode_struct, "kmaps")?.0)?;
+            if kmaps.len() != info.keymaps.len() {
+                differences.push(format!(
+                    "Expected {} kmaps in mode '{}', but found {}",
+                    info.keymaps.len(),
+                    mode,
+                    kmaps.len()
+                ));
+            }
+            for (address, kmap) in kmaps.iter().zip(info.kmap_paths()) {
+                let name = dereference(address)?;
+                let previous = *kmap_struct_names.entry(name).or_insert(kmap);
+                if previous != kmap {
+                    differences.push(format!(
+                        "Kmap struct '{}' is used for both '{}' and '{}'",
+                        name, previous.0, kmap.0
+                    ));
+                }
+            }
+        }
+
+        let seq_types: Vec<_> = self.sequences.seq_types().collect();
+        for (name, kmap) in kmap_struct_names {
+            let decoded =
+                KmapBuilder::decode(&symbols, name, &seq_types, &encoder)
+                    .with_context(|| {
+                        format!("Failed to decode kmap: '{}'", kmap.0)
+                    })?;
+            let actual = decoded
+                .into_iter()
+                .map(|lookup| {
+                    let key =
+                        (lookup.seq_type, lookup.chord, lookup.anagram.get());
+                    (key, lookup.sequence)
+                })
+                .collect();
+            let expected = self.expected_lookups(kmap)?;
+            differences.extend(
+                diff_lookups(expected, actual)
+                    .into_iter()
+                    .map(|difference| format!("{}: {}", kmap.0, difference)),
+            );
+        }
+
+        if differences.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::DecodeMismatch(differences))
+        }
+    }
+
+    fn expected_lookups(
+        &self,
+        kmap: &KmapPath,
+    ) -> Result<Vec<ExpectedLookup<'_>>, Error> {
+        let chord_map = self.chords.get_kmap(kmap)?;
+        let mut expected = Vec::new();
+        for &seq_type in self.sequences.seq_types() {
+            for name in self.sequences.get_seq_map(seq_type)?.names() {
+                if let Some(chord) = chord_map.get(name) {
+                    let key = (
+                        seq_type,
+                        self.chord_spec.to_firmware(chord)?.to_bytes(),
+                        chord.anagram_num.get(),
+                    );
+                    let seq = self.sequences.get(name, seq_type)?;
+                    expected.push((key, (name, seq)));
+                }
+            }
+        }
+        Ok(expected)
+    }
+}
+
+/// Compare the expected and decoded lookups, treating each as a multiset so
+/// that chords shared by several names are still checked.
+fn diff_lookups(
+    expected: Vec<ExpectedLookup>,
+    actual: Vec<(LookupKey, Sequence)>,
+) -> Vec<String> {
+    let mut remaining: BTreeMap<LookupKey, Vec<Sequence>> = BTreeMap::new();
+    for (key, seq) in actual {
+        remaining.entry(key).or_default().push(seq);
+    }
+
+    let mut differences = Vec::new();
+    let mut mismatched = Vec::new();
+    for (key, (name, seq)) in expected {
+        let decoded = remaining.get_mut(&key);
+        let found = decoded.as_ref().and_then(|decoded| {
+            decoded.iter().position(|other| same_sequence(seq, other))
+        });
+        match (found, decoded) {
+            (Some(i), Some(decoded)) => {
+                decoded.remove(i);
+            }
+            (None, Some(_)) => mismatched.push((key, name, seq)),
+            _ => differences.push(format!(
+                "{} '{}' is missing from the lookups",
+                key.0, name
+            )),
+        }
+    }
+
+    // Only report a mismatch once all the exact matches have been removed, so
+    // we know which decoded sequence it was supposed to be.
+    for (key, name, seq) in mismatched {
+        match remaining.get_mut(&key).and_then(|decoded| decoded.pop()) {
+            Some(other) => differences.push(format!(
+                "{} '{}' was encoded as '{}' but decodes as '{}'",
+                key.0,
+                name,
+                sequence_text(seq),
+                sequence_text(&other)
+            )),
+            None => differences.push(format!(
+                "{} '{}' is missing from the lookups",
+                key.0, name
+            )),
+        }
+    }
+
+    for ((seq_type, chord, anagram), decoded) in remaining {
+        for seq in decoded {
+            differences.push(format!(
+                "unexpected {} '{}' for chord {:?} (anagram {})",
+                seq_type,
+                sequence_text(&seq),
+                chord,
+                anagram
+            ));
+        }
+    }
+    differences
+}
+
+fn same_sequence(a: &Sequence, b: &Sequence) -> bool {
+    a.keypresses().eq(b.keypresses())
+}
+
+fn sequence_text(seq: &Sequence) -> String {
+    seq.keypresses()
+        .map(keypress_text)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn keypress_text(keypress: &KeyPress) -> String {
+    keypress
+        .mods
+        .iter()
+        .chain(keypress.key.iter())
+        .map(|code| code.to_string())
+        .collect::<Vec<_>>()
+        .join("+")
+}
@@ -1,10 +1,23 @@
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{BufReader, Read};
 use std::path::PathBuf;
+use std::time::Duration;
 
+use diff::SettingsDiff;
+use emulator::{render_text, Emulator, Output, SwitchEvent};
 use error::{Error, ResultExt};
-
 use input::load_all_data;
+use optimizer::{Change, DictionaryOptimizer, Suggestion};
+use tutor::{
+    generate_lessons, generate_word_lessons, split_text, Copier, LessonHistory,
+    LessonStats, ReviewSchedule, SlideLine, StatsRecorder,
+};
+use types::{
+    AllData, AnagramNum, BoardName, CTree, Chord, ChordMap, DiagnosticKind,
+    KmapConversion, KmapItem, KmapLayout, KmapOrder, KmapPath, ModeName, Name,
+    Pin, SeqType, SequenceEncoder, SequenceEncoding, Severity, Word,
+    WordFrequencies, WordSpacePosition, Wordlike,
+};
 
 fn expected_dir() -> PathBuf {
     PathBuf::from("src/tests/expected-outputs/")
@@ -17,21 +30,69 @@ fn actual_dir() -> PathBuf {
 #[test]
 fn big_settings_output() {
     let all_data =
-        load_all_data(&PathBuf::from("src/tests/settings/big-test.yaml"))
+        load_all_data(&PathBuf::from("src/tests/settings/big-test.yaml"), None)
             .unwrap();
 
     let name_base = "auto_config-big_test";
     all_data
         .save_without_message_as(&name_base)
         .expect("Failed to save configuration");
+    // The fragment field and tables aren't needed without fragments.
+    let config = read(&actual_dir().join("auto_config-big_test.cpp")).unwrap();
+    assert!(!config.contains("fragment"));
 
     assert_firmware_config_eq(name_base);
 }
 
+#[test]
+fn big_settings_fragments() {
+    let mut all_data =
+        load_all_data(&PathBuf::from("src/tests/settings/big-test.yaml"), None)
+            .unwrap();
+    all_data.encoder = SequenceEncoder::new(
+        SequenceEncoding::Fragments,
+        &all_data.sequences,
+        &all_data.dictionary,
+        None,
+    )
+    .unwrap();
+    assert!(!all_data.encoder.fragments.is_empty());
+
+    // How often words are typed changes their codes, but not which fragments
+    // are stored.
+    let counts = all_data
+        .dictionary
+        .iter()
+        .take(3)
+        .map(|word| (word.word.clone(), 1_000_000));
+    let weighted = SequenceEncoder::new(
+        SequenceEncoding::Fragments,
+        &all_data.sequences,
+        &all_data.dictionary,
+        Some(&WordFrequencies::from_counts(counts)),
+    )
+    .unwrap();
+    assert_eq!(weighted.fragments, all_data.encoder.fragments);
+
+    // Saving decodes the lookups again, and checks them against the settings.
+    all_data
+        .save_without_message_as("auto_config-big_test_fragments")
+        .expect("Failed to save configuration");
+    let config =
+        read(&actual_dir().join("auto_config-big_test_fragments.cpp")).unwrap();
+    assert!(config.contains("is_fragment") && config.contains("fragment_bits"));
+
+    let comparison = all_data.compare_encodings().unwrap();
+    assert_eq!(comparison.totals.len(), SequenceEncoding::ALL.len());
+    // The fragments shorten the sequences, but not by enough to pay for the
+    // fragment tables and the `is_fragment` field.
+    assert_eq!(comparison.smallest(), SequenceEncoding::Huffman);
+}
+
 #[test]
 fn chord22_output() {
     let all_data =
-        load_all_data(&PathBuf::from("src/tests/settings/test22.yaml"))
+        load_all_data(&PathBuf::from("src/tests/settings/test22.yaml"), None)
             .unwrap();
 
     let name_base = "auto_config-22";
@@ -43,6 +104,852 @@ fn chord22_output() {
     assert_firmware_config_eq(name_base);
 }
 
+#[test]
+fn chord22_emulator() {
+    let all_data =
+        load_all_data(&PathBuf::from("src/tests/settings/test22.yaml"), None)
+            .unwrap();
+
+    let typed = |chords: &[&[&str]]| {
+        let events = chords
+            .iter()
+            .enumerate()
+            .flat_map(|(i, names)| {
+                let chord = make_chord(&all_data, names);
+                SwitchEvent::tap(&chord, 100 * i as u32, 50)
+            })
+            .collect();
+        let outputs = Emulator::new(&all_data).unwrap().run(events).unwrap();
+        render_text(&outputs)
+    };
+
+    assert_eq!(typed(&[&["key_t", "key_h", "key_e"]]), " the");
+    assert_eq!(
+        typed(&[&["key_t", "key_h", "key_e", "mod_anagram_1"]]),
+        " teeth"
+    );
+    assert_eq!(
+        typed(&[
+            &["key_t", "key_h", "key_e", "mod_capital"],
+            &["key_t", "key_h", "key_e", "mod_shorten"],
+        ]),
+        " Ththe"
+    );
+    assert_eq!(typed(&[&["key_a"], &["mod_shift", "key_b"]]), "aB");
+    assert_eq!(typed(&[&["macro_ls"]]), "ls\n");
+}
+
+#[test]
+fn chord22_emulator_timing() {
+    let all_data =
+        load_all_data(&PathBuf::from("src/tests/settings/test22.yaml"), None)
+            .unwrap();
+    let t = make_chord(&all_data, &["key_t"]);
+    let h = make_chord(&all_data, &["key_h"]);
+    let e = make_chord(&all_data, &["key_e"]);
+
+    let typed = |offset: u32| {
+        let mut events = SwitchEvent::tap(&t, 0, 200);
+        events.extend(SwitchEvent::tap(&h, offset, 200 - offset));
+        events.extend(SwitchEvent::tap(&e, 2 * offset, 200 - 2 * offset));
+        let outputs = Emulator::new(&all_data).unwrap().run(events).unwrap();
+        render_text(&outputs)
+    };
+
+    // Switches pressed within the chord delay are grouped into one chord.
+    assert_eq!(typed(10), " the");
+    // Otherwise they're sent separately, as plain keys.
+    assert_eq!(typed(40), "the");
+
+    let outputs = Emulator::new(&all_data)
+        .unwrap()
+        .run(SwitchEvent::tap(
+            &make_chord(&all_data, &["key_z", "key_a"]),
+            0,
+            50,
+        ))
+        .unwrap();
+    match outputs.as_slice() {
+        [Output::Unknown(_)] => (),
+        _ => panic!("expected unknown chord, got {:?}", outputs),
+    }
+}
+
+#[test]
+fn chord22_verify_lookups() {
+    let all_data =
+        load_all_data(&PathBuf::from("src/tests/settings/test22.yaml"), None)
+            .unwrap();
+    let mut tree = all_data.render_main(false).unwrap();
+    all_data.verify_lookups(&tree).unwrap();
+
+    // Pair the chords with the wrong sequences, like a packing bug would.
+    let name = "kmap0_Plain_len5_anagram0_chords";
+    assert!(swap_first_values(&mut tree, name), "no array: {}", name);
+    match all_data.verify_lookups(&tree) {
+        Err(Error::DecodeMismatch(_)) => (),
+        result => panic!("expected decode mismatch, got {:?}", result),
+    }
+}
+
+#[test]
+fn chord22_diagnostics() {
+    let all_data =
+        load_all_data(&PathBuf::from("src/tests/settings/test22.yaml"), None)
+            .unwrap();
+    let diagnostics = all_data.diagnostics(&WordFrequencies::default());
+    assert!(diagnostics.iter().all(|d| d.severity == Severity::Warning));
+
+    let unused: Vec<_> = diagnostics
+        .iter()
+        .filter(|d| d.kind == DiagnosticKind::UnusedChord)
+        .collect();
+    assert_eq!(unused.len(), 2);
+    assert_eq!(unused[0].names, vec![Name::from("key_comma")]);
+    assert_eq!(
+        unused[0].kmap.as_ref().map(|kmap| kmap.0.as_str()),
+        Some("src/tests/settings/keymaps/test22.kmap")
+    );
+    assert_eq!((unused[0].line, unused[0].block), (Some(50), Some(2)));
+    assert_eq!(unused[1].names, vec![Name::from("key_period")]);
+
+    // "teeth" needs an anagram mod, so it shouldn't be more common than "the".
+    let counts = vec![("the".to_owned(), 1), ("Teeth".to_owned(), 2)];
+    let diagnostics =
+        all_data.diagnostics(&WordFrequencies::from_counts(counts));
+    let rarer_first: Vec<_> = diagnostics
+        .iter()
+        .filter(|d| d.kind == DiagnosticKind::RarerWordFirst)
+        .collect();
+    assert_eq!(rarer_first.len(), 1);
+    assert_eq!(
+        rarer_first[0].message,
+        "Rarer word 'the' has anagram 0, but the more common word 'teeth' \
+         has anagram 1"
+    );
+}
+
+#[test]
+fn chord22_suggest_dictionary() {
+    let all_data =
+        load_all_data(&PathBuf::from("src/tests/settings/test22.yaml"), None)
+            .unwrap();
+    let suggest = |frequencies: &WordFrequencies| {
+        DictionaryOptimizer::new(&all_data, frequencies)
+            .unwrap()
+            .run()
+            .unwrap()
+    };
+
+    // Without frequencies, the existing anagram numbers are kept.
+    let suggestions = suggest(&WordFrequencies::default());
+    assert!(suggestions.iter().all(|s| s.change == Change::Unchanged));
+
+    // More common words get lower anagram numbers.
+    let ranking = vec!["teeth".to_owned(), "the".to_owned()];
+    let suggestions = suggest(&WordFrequencies::from_ranking(ranking));
+    let anagram = |num| AnagramNum::new(num).unwrap();
+    assert_eq!(
+        suggestions[0].change,
+        Change::Anagram {
+            old: anagram(0),
+            new: anagram(1)
+        }
+    );
+    assert_eq!(
+        suggestions[1].change,
+        Change::Anagram {
+            old: anagram(1),
+            new: anagram(0)
+        }
+    );
+    assert_eq!(
+        Suggestion::render_all(&suggestions),
+        "dictionary:\n  \
+         - {word: \"the\", anagram: 1}\n  \
+         - {word: \"teeth\"}\n  \
+         - {word: \"1337\"}\n"
+    );
+}
+
+#[test]
+fn chord22_export() {
+    let mut all_data =
+        load_all_data(&PathBuf::from("src/tests/settings/test22.yaml"), None)
+            .unwrap();
+    let mut directory = actual_dir();
+    directory.push("export-22");
+    let _ = fs::remove_dir_all(&directory);
+
+    all_data
+        .export(&directory)
+        .expect("Failed to export sketch");
+    for file_name in &["export-22.ino", "Pipit.h", "auto_config.h"] {
+        assert!(directory.join(file_name).is_file(), "missing {}", file_name);
+    }
+    assert!(!directory.join("pipit-firmware.ino").exists());
+
+    // Exporting again should overwrite the previous export.
+    all_data
+        .export(&directory)
+        .expect("Failed to re-export sketch");
+
+    let missing = actual_dir().join("missing-firmware");
+    all_data.user_options.firmware_source_directory = missing.clone();
+    let error = all_data.export(&directory).unwrap_err().to_string();
+    assert!(error.contains(&format!("{:?}", missing)));
+}
+
+#[test]
+fn chord22_profiles() {
+    let path = PathBuf::from("src/tests/settings/test22.yaml");
+    let all_data = load_all_data(&path, None).unwrap();
+    assert_eq!(all_data.user_options.chord_delay.0, 30);
+    assert_eq!(all_data.modes.len(), 4);
+
+    let slow = load_all_data(&path, Some("slow")).unwrap();
+    assert_eq!(slow.user_options.chord_delay.0, 50);
+    assert_eq!(slow.user_options.held_delay.0, 200);
+    assert_eq!(slow.modes.len(), 1);
+
+    assert!(load_all_data(&path, Some("missing")).is_err());
+}
+
+#[test]
+fn chord22_diff() {
+    let path = PathBuf::from("src/tests/settings/test22.yaml");
+    let all_data = load_all_data(&path, None).unwrap();
+    let slow_data = load_all_data(&path, Some("slow")).unwrap();
+
+    assert!(SettingsDiff::new(&all_data, &all_data).unwrap().is_empty());
+
+    let diff = SettingsDiff::new(&all_data, &slow_data).unwrap();
+    let mode = |s: &str| ModeName(s.to_owned());
+    assert_eq!(
+        diff.removed_modes,
+        vec![
+            mode("gaming_mode"),
+            mode("left_hand_mode"),
+            mode("windows_mode")
+        ]
+    );
+    assert!(diff.added_modes.is_empty());
+    assert!(diff.modes.is_empty());
+    assert!(diff.words.is_empty());
+
+    // Swap the chords of two letters, and give another letter a new chord.
+    let kmap = fs::read_to_string("src/tests/settings/keymaps/test22.kmap")
+        .unwrap()
+        .replacen("key_a ", "key_x ", 1)
+        .replacen("key_o ", "key_a ", 1)
+        .replacen("key_x ", "key_o ", 1)
+        .replacen(
+            "....   ....\t\t ....   ....   \t....   ....\t  ....   ....",
+            "....   ....\t\t ....   ....   \t....   ....\t  ...*   ....",
+            1,
+        );
+    let kmap_path = actual_dir().join("test22-diff.kmap");
+    fs::write(&kmap_path, kmap).unwrap();
+    let settings = fs::read_to_string(&path).unwrap().replace(
+        "src/tests/settings/keymaps/test22.kmap",
+        &kmap_path.to_string_lossy(),
+    );
+    let settings_path = actual_dir().join("test22-diff.yaml");
+    fs::write(&settings_path, settings).unwrap();
+    let changed_data = load_all_data(&settings_path, None).unwrap();
+
+    let diff = SettingsDiff::new(&all_data, &changed_data).unwrap();
+    let name = |s: &str| Name::from(s);
+    assert_eq!(diff.modes.len(), all_data.modes.len());
+    for mode_diff in &diff.modes {
+        assert!(mode_diff.added.is_empty() && mode_diff.removed.is_empty());
+        assert_eq!(
+            mode_diff.changed,
+            vec![name("key_a"), name("key_i"), name("key_o")]
+        );
+        assert_eq!(
+            mode_diff.reassigned,
+            vec![
+                (vec![name("key_a")], vec![name("key_o")]),
+                (vec![name("key_o")], vec![name("key_a")]),
+            ]
+        );
+    }
+    assert!(diff.words.is_empty());
+}
+
+#[test]
+fn chord22_memory_report() {
+    let mut all_data =
+        load_all_data(&PathBuf::from("src/tests/settings/test22.yaml"), None)
+            .unwrap();
+    let report = all_data.memory_report().unwrap();
+    let kmap_total: usize = report.kmaps.iter().map(|(_, size)| size).sum();
+    let seq_type_total: usize = report.seq_types.values().sum();
+    assert!(report.huffman_table > 0);
+    assert!(kmap_total > 0);
+    // The kmap structs themselves aren't part of any seq type.
+    assert!(kmap_total > seq_type_total);
+    assert!(report.total > kmap_total + report.huffman_table);
+
+    // The Pro Micro has smaller pointers, and no padding.
+    let board_name = all_data.user_options.board_name;
+    all_data.user_options.board_name = BoardName::ProMicro;
+    assert!(all_data.memory_report().unwrap().total < report.total);
+    all_data.user_options.board_name = board_name;
+
+    all_data.user_options.memory_budget = Some(report.total - 1);
+    assert!(all_data
+        .save_without_message_as("auto_config-memory_budget")
+        .is_err());
+}
+
+#[test]
+fn huffman_corpus() {
+    let path = PathBuf::from("src/tests/settings/test22.yaml");
+    let word_bits = |profile| {
+        let report = load_all_data(&path, profile)
+            .unwrap()
+            .memory_report()
+            .unwrap();
+        report.compression[&SeqType::Word].1
+    };
+    // The words are common in the corpus, so they get shorter codes.
+    assert!(word_bits(Some("typing_log")) < word_bits(None));
+}
+
+#[test]
+fn review_schedule() {
+    let mut schedule = ReviewSchedule::default();
+    assert!(schedule.drill_slides(0).is_empty());
+
+    let start = 1_000_000;
+    let letters = ["a", "b", "c", "d", "e", "f"];
+    for letter in &letters {
+        schedule.record(letter, true, start);
+    }
+    schedule.record("b", false, start);
+    schedule.record("b", false, start);
+    schedule.record("c", false, start);
+    assert_eq!(schedule.weakest(2, start), vec!["b", "c"]);
+    assert!(schedule.get("b").unwrap().ease < schedule.get("a").unwrap().ease);
+
+    // Correct answers only lengthen the interval once the chord is due.
+    let interval = schedule.get("a").unwrap().interval;
+    schedule.record("a", true, start + 1);
+    assert_eq!(schedule.get("a").unwrap().interval, interval);
+    schedule.record("a", true, start + interval);
+    assert!(schedule.get("a").unwrap().interval > interval);
+
+    // Every letter that needs practice is drilled on every slide.
+    let slides = schedule.drill_slides(start);
+    assert!(!slides.is_empty());
+    for slide in &slides {
+        match slide.line {
+            SlideLine::Letters(ref line) => {
+                for letter in &letters {
+                    assert!(line.contains(letter), "{} not in {}", letter, line)
+                }
+            }
+            _ => panic!("Drill slides should be letters"),
+        }
+    }
+}
+
+#[test]
+fn lesson_stats() {
+    let mut recorder = StatsRecorder::default();
+    recorder.record_char("a", true, None);
+    recorder.record_char("b", false, Some(Duration::from_millis(900)));
+    recorder.record_char("b", true, Some(Duration::from_millis(500)));
+    recorder.record_char(" ", true, Some(Duration::from_millis(2000)));
+    recorder.end_slide(2., Duration::from_secs(30));
+    recorder.record_char("a", true, None);
+    recorder.record_char("c", true, Some(Duration::from_millis(100)));
+    recorder.end_slide(1., Duration::from_secs(30));
+    let stats = recorder.finish("1) test", ModeName::default(), 0);
+
+    assert_eq!(stats.slides.len(), 2);
+    assert_eq!(stats.slides[0].words_per_minute(), 4);
+    let total = stats.total();
+    assert_eq!(total.words_per_minute(), 3);
+    assert_eq!(total.typed, 6);
+    assert_eq!(total.errors, 1);
+    // Whitespace and untimed chords aren't included.
+    assert_eq!(stats.slowest(5), vec![("b", 700), ("c", 100)]);
+
+    let mut history = LessonHistory::default();
+    history.add(stats.clone());
+    history.add(stats.clone());
+    assert_eq!(history.by_lesson()["1) test"].len(), 2);
+
+    // Only the most recent completions are kept.
+    for completed in 0..100 {
+        history.add(LessonStats {
+            completed,
+            ..stats.clone()
+        });
+    }
+    let completions = &history.by_lesson()["1) test"];
+    assert!(completions.len() < 100);
+    assert_eq!(completions.last().unwrap().completed, 99);
+    assert_eq!(completions[0].completed as usize, 100 - completions.len());
+
+    // An outdated history is moved aside, instead of stopping the tutor.
+    let path = actual_dir().join("history.yaml");
+    let old_path = actual_dir().join("history.yaml.old");
+    fs::remove_file(&old_path).ok();
+    fs::write(&path, "- lesson: 1) test\n  outdated_field: 0\n").unwrap();
+    assert!(LessonHistory::load(&path).is_empty());
+    assert!(!path.exists() && old_path.exists());
+}
+
+#[test]
+fn generated_lessons() {
+    let all_data =
+        load_all_data(&PathBuf::from("src/tests/settings/big-test.yaml"), None)
+            .unwrap();
+    let tutor_data = all_data.get_tutor_data().unwrap();
+    let lessons =
+        generate_lessons(&tutor_data, &ModeName::from("default_mode"));
+    let names: Vec<_> = lessons.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names[0], "1) single switch chords part 1");
+    let position = |name: &str| {
+        names
+            .iter()
+            .position(|n| n.ends_with(name))
+            .unwrap_or_else(|| panic!("missing lesson: {}", name))
+    };
+    assert!(position("left hand chords") < position("right hand chords"));
+    assert!(position("right hand chords") < position("symbols part 1"));
+    assert!(names.last().unwrap().ends_with("dictionary words"));
+}
+
+#[test]
+fn generated_word_lessons() {
+    let all_data =
+        load_all_data(&PathBuf::from("src/tests/settings/big-test.yaml"), None)
+            .unwrap();
+    let tutor_data = all_data.get_tutor_data().unwrap();
+    let lessons =
+        generate_word_lessons(&tutor_data, &ModeName::from("default_mode"));
+    let names: Vec<_> = lessons.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec![
+            "1) word chords part 1",
+            "2) word chords part 2",
+            "3) word chords part 3",
+            "4) word chords part 4",
+            "5) anagrams",
+            "6) word modifiers",
+            "7) fixing words",
+        ]
+    );
+}
+
+#[test]
+fn copier_whole_words() {
+    let all_data =
+        load_all_data(&PathBuf::from("src/tests/settings/big-test.yaml"), None)
+            .unwrap();
+    let mut tutor_data = all_data.get_tutor_data().unwrap();
+    tutor_data.word_space_position = WordSpacePosition::After;
+    let mode = ModeName::from("default_mode");
+    let lessons = generate_word_lessons(&tutor_data, &mode);
+    let (_, fixing) = lessons
+        .iter()
+        .find(|(name, _)| name.ends_with("fixing words"))
+        .unwrap();
+    let slide = fixing
+        .slides()
+        .iter()
+        .find(|slide| slide.instruction == "Type the word, then capitalize it")
+        .unwrap();
+    let words: Vec<_> = match &slide.line {
+        SlideLine::Words { words, .. } => words
+            .iter()
+            .map(|word| word.text.clone())
+            .filter(|text| !text.is_empty())
+            .collect(),
+        SlideLine::Letters(_) => panic!("expected a line of words"),
+    };
+    assert!(words.len() > 2);
+    assert!(words
+        .iter()
+        .all(|word| word.ends_with(' ') && !word.starts_with(' ')));
+
+    let chord = |name: &Name| tutor_data.chord(name, &mode);
+    let mut copier = Copier::new(79);
+    copier.start_line_with(&slide.line, chord).unwrap();
+    let type_word = |copier: &mut Copier, word: &str| {
+        word.chars().all(|c| copier.type_char(c).is_correct())
+    };
+
+    assert!(type_word(&mut copier, &words[0]));
+    assert_eq!(
+        copier.take_typed(),
+        Some((words[0].trim().to_owned(), true))
+    );
+
+    // Typing the word wrong should hint the command that fixes it. The
+    // command's backspaces are allowed, but only within the current word.
+    let wrong = words[1].to_lowercase();
+    assert!(!copier.type_char(wrong.chars().next().unwrap()).is_correct());
+    assert_eq!(copier.take_typed(), None);
+    let hint = copier.next_hint().unwrap().unwrap();
+    assert_eq!(
+        hint.chord,
+        chord(&Name::from("command_cycle_capital")).unwrap()
+    );
+    assert!(copier.type_backspace().is_correct());
+    assert!(copier.type_backspace().is_correct());
+    assert!(type_word(&mut copier, &words[1]));
+    assert_eq!(
+        copier.take_typed(),
+        Some((words[1].trim().to_owned(), false))
+    );
+
+    let (last, middle) = words[2..].split_last().unwrap();
+    for word in middle {
+        assert!(type_word(&mut copier, word));
+    }
+    assert!(!copier.at_end_of_line());
+    assert!(type_word(&mut copier, last));
+    assert!(copier.at_end_of_line());
+}
+
+#[test]
+fn text_file_lines() {
+    let text =
+        "Down the  rabbit-hole,\r\nsaid Alice.\r\n \r\n\r\n“Curiouser”\n";
+    let can_type = |letter: &str| letter.is_ascii();
+    let (lines, skipped) = split_text(text, can_type);
+    assert_eq!(
+        lines,
+        vec!["Down the rabbit-hole, said Alice.", "Curiouser"]
+    );
+    assert_eq!(skipped.into_iter().collect::<Vec<_>>(), vec!["“", "”"]);
+
+    let long = "word ".repeat(200);
+    let (lines, _) = split_text(&long, can_type);
+    assert!(lines.len() > 1);
+    assert!(lines.iter().all(|line| line.len() <= 500));
+}
+
+#[test]
+fn settings_error_location() {
+    let misspell = |original: &str, copy: &str| {
+        let settings = fs::read_to_string(original).unwrap().replacen(
+            "  chord_delay:",
+            "  chord_dealy:",
+            1,
+        );
+        let path = actual_dir().join(copy);
+        fs::write(&path, settings).unwrap();
+        load_all_data(&path, None).unwrap_err().to_string()
+    };
+
+    let error =
+        misspell("src/tests/settings/big-test.yaml", "misspelled-option.yaml");
+    assert!(error.contains("options: unknown field `chord_dealy`"));
+    assert!(error.contains("line 3 column 3"));
+
+    // With includes, the error can only name the file and section.
+    let error =
+        misspell("src/tests/settings/test22.yaml", "misspelled-include.yaml");
+    assert!(error.contains("misspelled-include.yaml"));
+    assert!(error.contains("Invalid section: 'options'"));
+    assert!(error.contains("unknown field `chord_dealy`"));
+}
+
+#[test]
+fn include_conflict() {
+    let path = PathBuf::from("src/tests/settings/include-conflict.yaml");
+    let error = load_all_data(&path, None).unwrap_err().to_string();
+    assert!(error.contains(
+        "Defined in both \"src/tests/settings/test22-dictionary.yaml\" and \
+         \"src/tests/settings/test22-dictionary.yaml\""
+    ));
+
+    let path = PathBuf::from("src/tests/settings/include-word-conflict.yaml");
+    let error = load_all_data(&path, None).unwrap_err().to_string();
+    assert!(error.contains("There cannot be more than one 'the'"));
+    assert!(error.contains(
+        "Defined in both \"src/tests/settings/test22-dictionary.yaml\" and \
+         \"src/tests/settings/test22-anagrams.yaml\""
+    ));
+}
+
+#[test]
+fn word_list() {
+    let path = actual_dir().join("word-list.txt");
+    fs::write(&path, "# comment\n\nthe\nteeth 1\nacknowledge 0 ack\n").unwrap();
+    let words = Word::load_word_list(&path).unwrap();
+    let entries: Vec<_> = words
+        .iter()
+        .map(|(line, w)| {
+            (
+                *line,
+                w.word.as_str(),
+                w.anagram_num().get(),
+                w.chord.as_ref(),
+            )
+        })
+        .collect();
+    let ack = "ack".to_owned();
+    assert_eq!(
+        entries,
+        vec![
+            (3, "the", 0, None),
+            (4, "teeth", 1, None),
+            (5, "acknowledge", 0, Some(&ack))
+        ]
+    );
+
+    fs::write(&path, "the 1 t extra\n").unwrap();
+    assert!(Word::load_word_list(&path).is_err());
+}
+
+#[test]
+fn word_list_conflict() {
+    let word_list = actual_dir().join("word-list-conflict.txt");
+    fs::write(&word_list, "teeth 2\nthe\n").unwrap();
+    let settings = fs::read_to_string("src/tests/settings/test22.yaml")
+        .unwrap()
+        .replace(
+            "  - src/tests/settings/test22-words.txt\n",
+            &format!(
+                "  - src/tests/settings/test22-words.txt\n  - {}\n",
+                word_list.display()
+            ),
+        );
+    let settings_path = actual_dir().join("word-list-conflict.yaml");
+    fs::write(&settings_path, settings).unwrap();
+
+    let error = load_all_data(&settings_path, None).unwrap_err().to_string();
+    assert!(error.contains(&format!(
+        "Defined in both the 'dictionary' section and {:?}, line 2",
+        word_list
+    )));
+}
+
+#[test]
+fn fmt_kmap() {
+    let all_data =
+        load_all_data(&PathBuf::from("src/tests/settings/test22.yaml"), None)
+            .unwrap();
+    let format = &all_data.user_options.kmap_format;
+    let original =
+        KmapPath("src/tests/settings/keymaps/test22.kmap".to_owned());
+    let formatted = KmapPath(
+        actual_dir()
+            .join("test22-formatted.kmap")
+            .to_string_lossy()
+            .into_owned(),
+    );
+
+    for &sort in &[false, true] {
+        let text = original.read_file(format).unwrap().format(sort);
+        fs::write(&formatted.0, &text).unwrap();
+        let chord_map = formatted.read(format).unwrap();
+        for item in &original.read_file(format).unwrap().items {
+            if let KmapItem::Section(section) = item {
+                for (name, chord) in section.names.iter().zip(&section.chords) {
+                    assert_eq!(chord_map.get(name), Some(chord));
+                }
+            }
+        }
+        // Formatting again shouldn't change anything.
+        assert_eq!(formatted.read_file(format).unwrap().format(sort), text);
+    }
+}
+
+#[test]
+fn write_chord_map() {
+    let settings_path = PathBuf::from("src/tests/settings/test22.yaml");
+    let all_data = load_all_data(&settings_path, None).unwrap();
+    let format = &all_data.user_options.kmap_format;
+    let original =
+        KmapPath("src/tests/settings/keymaps/test22.kmap".to_owned());
+    let written = actual_dir().join("test22-written.kmap");
+
+    original
+        .read(format)
+        .unwrap()
+        .to_kmap_file(KmapLayout::plain(format))
+        .unwrap()
+        .save(&written)
+        .unwrap();
+
+    // Load the same settings with the written kmap instead of the original.
+    // The dictionary chords are generated again, so they should all match.
+    let written = KmapPath(written.to_string_lossy().into_owned());
+    let settings = fs::read_to_string(&settings_path)
+        .unwrap()
+        .replace(&original.0, &written.0);
+    let written_settings_path = actual_dir().join("test22-written.yaml");
+    fs::write(&written_settings_path, settings).unwrap();
+    let reloaded = load_all_data(&written_settings_path, None).unwrap();
+    let chord_map = all_data.chords.get_kmap(&original).unwrap();
+    let reloaded_map = reloaded.chords.get_kmap(&written).unwrap();
+    assert_eq!(reloaded_map.names().count(), chord_map.names().count());
+    for (name, chord) in chord_map.iter() {
+        assert_eq!(reloaded_map.get(name), Some(chord));
+    }
+
+    // Anagram numbers can't be written, so they shouldn't be dropped silently.
+    let (name, chord) = chord_map
+        .iter()
+        .find(|(name, _)| chord_map.span(name).is_none())
+        .unwrap();
+    let mut generated = ChordMap::default();
+    generated.insert(name.to_owned(), chord.to_owned()).unwrap();
+    let text = generated
+        .to_kmap_file(KmapLayout::plain(format))
+        .unwrap()
+        .format(false);
+    assert!(text.contains(&name.0));
+    let mut chord = chord.clone();
+    chord.anagram_num = AnagramNum::new(1).unwrap();
+    let mut anagram_map = ChordMap::default();
+    anagram_map.insert(name.to_owned(), chord).unwrap();
+    assert!(anagram_map.to_kmap_file(KmapLayout::plain(format)).is_err());
+}
+
+#[test]
+fn write_mode_kmap() {
+    let all_data =
+        load_all_data(&PathBuf::from("src/tests/settings/test22.yaml"), None)
+            .unwrap();
+    let format = &all_data.user_options.kmap_format;
+    let mode = ModeName::from("default_mode");
+    let written = actual_dir().join("test22-default-mode.kmap");
+    all_data.write_mode_kmap(&mode, &written).unwrap();
+
+    // Word chords are written with their anagram modifiers, like the tutor
+    // shows them.
+    let written = KmapPath(written.to_string_lossy().into_owned())
+        .read(format)
+        .unwrap();
+    let tutor_data = all_data.get_tutor_data().unwrap();
+    let expected = &tutor_data.chords[&mode];
+    assert!(expected.keys().any(|name| name.0.starts_with("word_")));
+    assert_eq!(written.names().count(), expected.len());
+    for (name, chord) in expected {
+        assert_eq!(written.get(name), Some(chord));
+    }
+}
+
+#[test]
+fn mirror_kmap() {
+    let all_data =
+        load_all_data(&PathBuf::from("src/tests/settings/test22.yaml"), None)
+            .unwrap();
+    let format = &all_data.user_options.kmap_format;
+    let original =
+        KmapPath("src/tests/settings/keymaps/test22.kmap".to_owned());
+    let mirrored = actual_dir().join("test22-mirrored.kmap");
+    let mirrored_twice = actual_dir().join("test22-mirrored-twice.kmap");
+
+    all_data
+        .transform_kmap("mirror", &original, &mirrored)
+        .unwrap();
+    let mirrored = KmapPath(mirrored.to_string_lossy().into_owned());
+    all_data
+        .transform_kmap("mirror", &mirrored, &mirrored_twice)
+        .unwrap();
+
+    let chords = original.read(format).unwrap();
+    let mirrored_chords = mirrored.read(format).unwrap();
+    let mirrored_twice_chords =
+        KmapPath(mirrored_twice.to_string_lossy().into_owned())
+            .read(format)
+            .unwrap();
+    let name = |s: &str| Name(s.to_owned());
+    assert_eq!(
+        mirrored_chords.get(&name("mod_shift")),
+        chords.get(&name("mod_alt"))
+    );
+    for (name, chord) in chords.iter() {
+        assert_eq!(mirrored_twice_chords.get(name), Some(chord));
+    }
+    assert!(all_data.user_options.kmap_transform("flip").is_err());
+}
+
+#[test]
+fn convert_kmap() {
+    let all_data =
+        load_all_data(&PathBuf::from("src/tests/settings/test22.yaml"), None)
+            .unwrap();
+    let conversion_path =
+        PathBuf::from("src/tests/settings/test22-small-conversion.yaml");
+    let original =
+        KmapPath("src/tests/settings/keymaps/test22.kmap".to_owned());
+    let converted = actual_dir().join("test22-small.kmap");
+
+    all_data
+        .convert_kmap(&conversion_path, &original, &converted)
+        .unwrap();
+
+    let conversion = KmapConversion::load(&conversion_path).unwrap();
+    let chords = KmapPath(converted.to_string_lossy().into_owned())
+        .read(&conversion.kmap_format)
+        .unwrap();
+    let name = |s: &str| Name(s.to_owned());
+    let bits = |chord: Option<&Chord<KmapOrder>>| {
+        chord.map(|chord| chord.iter().collect::<Vec<_>>())
+    };
+    assert_eq!(
+        bits(chords.get(&name("key_a"))),
+        Some(vec![false, false, false, false, true, false, false, false])
+    );
+    // These use switches that don't exist on the smaller keyboard.
+    assert!(chords.get(&name("key_h")).is_none());
+    assert!(chords.get(&name("mod_shift")).is_none());
+}
+
+#[test]
+fn board_pins() {
+    let all_data =
+        load_all_data(&PathBuf::from("src/tests/settings/test22.yaml"), None)
+            .unwrap();
+    assert!(all_data.user_options.validate_board().is_ok());
+
+    assert!(BoardName::TeensyLC.validate_pin(Pin(26)).is_ok());
+    assert!(BoardName::TeensyLC.validate_pin(Pin(27)).is_err());
+    // Pin 12 isn't broken out on the Pro Micro.
+    assert!(BoardName::ProMicro.validate_pin(Pin(12)).is_err());
+    assert!(BoardName::ProMicro.validate_pin(Pin(14)).is_ok());
+}
+
+fn swap_first_values(tree: &mut CTree, array_name: &str) -> bool {
+    match tree {
+        CTree::Array { name, values, .. } if name.0 == array_name => {
+            values.swap(0, 1);
+            true
+        }
+        CTree::Group(trees) => trees
+            .iter_mut()
+            .any(|tree| swap_first_values(tree, array_name)),
+        CTree::Namespace { contents, .. } | CTree::Ifndef { contents, .. } => {
+            swap_first_values(contents, array_name)
+        }
+        _ => false,
+    }
+}
+
+fn make_chord(all_data: &AllData, names: &[&str]) -> Chord<KmapOrder> {
+    names
+        .iter()
+        .map(|name| {
+            all_data
+                .get_chord_in_mode(&Name::from(*name), &ModeName::default())
+                .expect("chord not found")
+        })
+        .fold(all_data.chord_spec.new_chord(), |a, b| a.union(&b).unwrap())
+}
+
 fn assert_firmware_config_eq(name_base: &str) {
     let expected_dir = expected_dir();
     let actual_dir = actual_dir();
@@ -7,6 +7,7 @@ use cursive::Printer;
 
 use error::Error;
 use tutor::{offset, LabeledChord, SlideEntry, SlideLine, State};
+use types::{Chord, KmapOrder, Name};
 use unicode_segmentation::UnicodeSegmentation;
 
 #[derive(Debug, Clone)]
@@ -15,6 +16,9 @@ pub struct Copier {
     point_marker: String,
     point_offset: usize,
     line: CopierLine,
+    /// The most recently finished letter or word, and whether it was typed
+    /// correctly.
+    typed: Option<(String, bool)>,
 }
 
 #[derive(Debug, Clone)]
@@ -25,6 +29,15 @@ struct CopierLine {
     show_errors: bool,
     hint_map: HashMap<usize, LabeledChord>,
     show_hints_within_words: bool,
+    whole_words: bool,
+    /// The text of each word, by its start position. Only used for whole
+    /// words.
+    word_map: HashMap<usize, String>,
+    /// Commands that fix a mistyped word, by the word's start position.
+    fix_map: HashMap<usize, LabeledChord>,
+    /// Where the word that's currently being typed starts.
+    word_start: usize,
+    word_correct: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -41,10 +54,14 @@ impl Copier {
             point_offset,
             point_marker: "▼".into(),
             line: CopierLine::default(),
+            typed: None,
         }
     }
 
     pub fn next_hint(&mut self) -> Result<Option<LabeledChord>, Error> {
+        if self.line.whole_words {
+            return Ok(self.word_hint());
+        }
         let word_edge_hint = self.line.hint_map.get(&self.line.index);
         if word_edge_hint.is_some() {
             return Ok(word_edge_hint.cloned());
@@ -71,6 +88,35 @@ impl Copier {
         Ok(letter_hint)
     }
 
+    fn word_hint(&self) -> Option<LabeledChord> {
+        let start = self.line.word_start;
+        match self.line.word_map.get(&start) {
+            Some(word) if !word.starts_with(&self.typed_word()) => self
+                .line
+                .fix_map
+                .get(&start)
+                .cloned()
+                .or_else(LabeledChord::backspace),
+            Some(_) => self.line.hint_map.get(&start).cloned(),
+            None => LabeledChord::from_letter("\n"),
+        }
+    }
+
+    /// Everything typed since the start of the current word.
+    fn typed_word(&self) -> String {
+        self.line
+            .actual
+            .graphemes(true)
+            .skip(self.extra_spaces() + self.line.word_start)
+            .collect()
+    }
+
+    /// Take the most recently finished letter or word, if it hasn't been
+    /// taken yet.
+    pub fn take_typed(&mut self) -> Option<(String, bool)> {
+        self.typed.take()
+    }
+
     /// Offset of the most recently typed character
     fn prev_offset(&self) -> usize {
         self.point_offset - 1
@@ -94,6 +140,13 @@ impl Copier {
     }
 
     pub fn type_char(&mut self, character: char) -> PrevCharStatus {
+        if self.line.whole_words {
+            // A word's chord types many characters, so they're only checked
+            // once the whole word has been typed.
+            self.line.actual.push(character);
+            self.line.index += 1;
+            return self.word_status();
+        }
         let expected = self.expected_next();
         let actual = character.to_string();
         let status = self.prev_char_status(&actual, &expected);
@@ -105,10 +158,37 @@ impl Copier {
         }
         if let Some(e) = &expected {
             State::update_learn_state(e.clone(), status.is_correct());
+            self.typed = Some((e.clone(), status.is_correct()));
         }
         status
     }
 
+    /// Check the part of the current word that's been typed so far, and move
+    /// on to the next word once it's done.
+    fn word_status(&mut self) -> PrevCharStatus {
+        let word = match self.line.word_map.get(&self.line.word_start) {
+            Some(word) => word.clone(),
+            // Typed past the end of the line
+            None => return PrevCharStatus::Incorrect(None),
+        };
+        let typed = self.typed_word();
+        if typed == word {
+            self.typed = Some((word.trim().to_owned(), self.line.word_correct));
+            self.line.word_start = self.line.index;
+            self.line.word_correct = true;
+            PrevCharStatus::Correct
+        } else if word.starts_with(&typed) {
+            PrevCharStatus::Correct
+        } else {
+            self.line.word_correct = false;
+            if self.line.show_errors {
+                PrevCharStatus::Incorrect(None)
+            } else {
+                PrevCharStatus::Correct
+            }
+        }
+    }
+
     fn prev_char_status(
         &self,
         actual: &str,
@@ -118,11 +198,21 @@ impl Copier {
         if correct || !self.line.show_errors {
             PrevCharStatus::Correct
         } else {
-            PrevCharStatus::Incorrect(LabeledChord::from_letter(&actual))
+            PrevCharStatus::Incorrect(LabeledChord::from_letter(actual))
         }
     }
 
     pub fn type_backspace(&mut self) -> PrevCharStatus {
+        if self.line.whole_words {
+            // Commands that fix words type backspaces, so always allow them
+            // within the current word.
+            if self.line.index > self.line.word_start {
+                self.line.actual.pop();
+                self.line.index -= 1;
+                return self.word_status();
+            }
+            return PrevCharStatus::Correct;
+        }
         if !State::allow_mistakes() {
             // Don't let the user type backspace, show it as incorrect
             return PrevCharStatus::Incorrect(LabeledChord::backspace());
@@ -138,13 +228,31 @@ impl Copier {
     }
 
     pub fn start_line(&mut self, line: &SlideLine) -> Result<(), Error> {
-        self.line = CopierLine::from(line, self.extra_spaces())?;
+        self.start_line_with(line, State::chord)
+    }
+
+    /// Like `start_line()`, but look up the chords of the line's words with
+    /// the given function instead of from the tutor's state.
+    pub fn start_line_with<F>(
+        &mut self,
+        line: &SlideLine,
+        chord: F,
+    ) -> Result<(), Error>
+    where
+        F: Fn(&Name) -> Result<Chord<KmapOrder>, Error>,
+    {
+        let (entries, text) = line.to_entries(chord)?;
+        self.line = CopierLine::from(line, entries, text, self.extra_spaces());
         Ok(())
     }
 
     pub fn at_end_of_line(&self) -> bool {
-        self.line.actual.graphemes(true).count()
-            >= self.line.expected.graphemes(true).count()
+        let typed_all = self.line.actual.graphemes(true).count()
+            >= self.line.expected.graphemes(true).count();
+        // Whole words must also be finished correctly.
+        typed_all
+            && (!self.line.whole_words
+                || self.line.word_start == self.line.index)
     }
 
     fn extra_spaces(&self) -> usize {
@@ -240,21 +348,40 @@ impl View for Copier {
 }
 
 impl CopierLine {
-    fn from(line: &SlideLine, extra_spaces: usize) -> Result<Self, Error> {
-        let (entries, text) = line.to_entries()?;
-
+    fn from(
+        line: &SlideLine,
+        entries: Vec<SlideEntry>,
+        text: String,
+        extra_spaces: usize,
+    ) -> Self {
         let pad = " ".repeat(extra_spaces);
         let expected = pad.clone() + &text;
         let mut actual = pad;
         actual.reserve(expected.len());
-        Ok(Self {
+        let whole_words = line.whole_words();
+        let hint_map = if whole_words {
+            let words: Vec<_> = entries
+                .iter()
+                .filter(|entry| !entry.text.is_empty())
+                .cloned()
+                .collect();
+            make_hint_map(&words)
+        } else {
+            make_hint_map(&entries)
+        };
+        Self {
             expected,
             actual,
             index: 0,
             show_errors: line.show_errors(),
-            hint_map: make_hint_map(&entries),
+            hint_map,
             show_hints_within_words: !line.has_length_overrides(),
-        })
+            whole_words,
+            word_map: make_word_map(&entries),
+            fix_map: make_fix_map(&entries),
+            word_start: 0,
+            word_correct: true,
+        }
     }
 }
 
@@ -267,6 +394,11 @@ impl Default for CopierLine {
             show_errors: true,
             hint_map: HashMap::new(),
             show_hints_within_words: true,
+            whole_words: false,
+            word_map: HashMap::new(),
+            fix_map: HashMap::new(),
+            word_start: 0,
+            word_correct: true,
         }
     }
 }
@@ -323,6 +455,31 @@ fn make_hint_map(entries: &[SlideEntry]) -> HashMap<usize, LabeledChord> {
     map
 }
 
+fn make_word_map(entries: &[SlideEntry]) -> HashMap<usize, String> {
+    let mut position = 0;
+    let mut map = HashMap::new();
+    for entry in entries {
+        if !entry.text.is_empty() {
+            map.insert(position, entry.text.clone());
+        }
+        position += entry.len();
+    }
+    map
+}
+
+/// Map each word to the command right after it, if it has no text.
+fn make_fix_map(entries: &[SlideEntry]) -> HashMap<usize, LabeledChord> {
+    let mut position = 0;
+    let mut map = HashMap::new();
+    for pair in entries.windows(2) {
+        if !pair[0].text.is_empty() && pair[1].text.is_empty() {
+            map.insert(position, pair[1].to_labeled_chord());
+        }
+        position += pair[0].len();
+    }
+    map
+}
+
 fn needs_hint(letter: &str) -> bool {
     !State::is_learned(letter).unwrap_or(false)
 }
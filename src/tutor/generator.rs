@@ -0,0 +1,581 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use tutor::{
+    get_switch_positions, mixed_groups, LessonConfig, Slide, SlideLine,
+    SlideWord,
+};
+use types::{
+    AnagramNum, Chord, KmapOrder, ModeName, Name, TutorData, Word,
+    WordSpacePosition, Wordlike,
+};
+
+const LETTERS_PER_LESSON: usize = 6;
+const DRILL_SLIDES: usize = 3;
+const GROUPS_PER_LINE: usize = 8;
+const GROUP_LENGTH: usize = 4;
+const WORD_SLIDES: usize = 4;
+const FINAL_WORD_SLIDES: usize = 12;
+const WORDS_PER_LINE: usize = 8;
+
+const WORD_CHORD_LESSONS: usize = 4;
+const WORDS_PER_CHORD_LESSON: usize = 8;
+const WORDS_PER_DRILL: usize = 4;
+const ANAGRAM_PAIRS: usize = 8;
+const PAIRS_PER_LINE: usize = 2;
+const MODIFIER_WORDS: usize = 4;
+
+/// Switches in the tutor's graphic that are left of this column are pressed
+/// by the left hand.
+const MIDDLE_COLUMN: usize = 39;
+const RIGHT_COLUMN: usize = 72;
+const COLUMN_WIDTH: usize = 6;
+const THUMB_ROW: usize = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Hand {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Finger {
+    Index,
+    Middle,
+    Ring,
+    Pinky,
+    Thumb,
+}
+
+/// Which fingers press a chord. Letters are introduced one group at a time,
+/// in this order, so that similar chords are learned together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ChordGroup {
+    SingleSwitch,
+    Finger(Hand, Finger),
+    Hand(Hand),
+    BothHands,
+    /// Chords with more than 2 switches, or switches that aren't in the
+    /// tutor's graphic.
+    Other,
+    /// Numbers and punctuation.
+    Symbols,
+}
+
+/// Make a sequence of lessons that teach every letter in the mode, using only
+/// the user's own chords. Each lesson introduces a few letters, drills them,
+/// and then practices dictionary words that only use letters learned so far.
+pub fn generate_lessons(
+    data: &TutorData,
+    mode: &ModeName,
+) -> Vec<(String, LessonConfig)> {
+    let mut groups: BTreeMap<ChordGroup, Vec<String>> = BTreeMap::new();
+    for (letter, chord) in letters(data, mode) {
+        groups
+            .entry(ChordGroup::new(&letter, &chord))
+            .or_default()
+            .push(letter);
+    }
+
+    let mut words: Vec<&str> = Vec::new();
+    for word in &data.dictionary {
+        if !words.contains(&word.word.as_str()) {
+            words.push(&word.word);
+        }
+    }
+
+    let mut learned = BTreeSet::new();
+    let mut lessons = Vec::new();
+    for (group, letters) in groups {
+        let chunks: Vec<_> = letters.chunks(LETTERS_PER_LESSON).collect();
+        for (i, new) in chunks.iter().enumerate() {
+            learned.extend(new.iter().flat_map(|letter| letter.chars()));
+            let description = if chunks.len() > 1 {
+                format!("{} part {}", group, i + 1)
+            } else {
+                group.to_string()
+            };
+            lessons.push((
+                format!("{}) {}", lessons.len() + 1, description),
+                letter_lesson(group, new, &learned, &words),
+            ));
+        }
+    }
+
+    let slides = word_slides(&words, &learned, &[], FINAL_WORD_SLIDES);
+    if !slides.is_empty() {
+        lessons.push((
+            format!("{}) dictionary words", lessons.len() + 1),
+            LessonConfig::new(
+                slides,
+                "Now practice typing words with all the letters.".to_owned(),
+                Vec::new(),
+            ),
+        ));
+    }
+    lessons
+}
+
+/// Every printable ascii letter with a chord in the mode, except for
+/// capitals.
+fn letters(
+    data: &TutorData,
+    mode: &ModeName,
+) -> Vec<(String, Chord<KmapOrder>)> {
+    let mut letters = Vec::new();
+    for (spelling, name) in &data.spellings.0 {
+        if !spelling.0.is_ascii_graphic() || spelling.is_uppercase() {
+            continue;
+        }
+        if let Ok(chord) = data.chord(name, mode) {
+            if !chord.is_empty() {
+                letters.push((spelling.to_string(), chord));
+            }
+        }
+    }
+    letters
+}
+
+fn letter_lesson(
+    group: ChordGroup,
+    new: &[String],
+    learned: &BTreeSet<char>,
+    words: &[&str],
+) -> LessonConfig {
+    let instruction = format!("Practice the new letters: {}", new.join(" "));
+    let mut slides: Vec<_> = (0..DRILL_SLIDES)
+        .map(|seed| Slide {
+            instruction: instruction.clone(),
+            line: SlideLine::Letters(mixed_groups(
+                new,
+                GROUPS_PER_LINE,
+                GROUP_LENGTH,
+                seed,
+            )),
+        })
+        .collect();
+
+    if learned.len() > new.len() {
+        let all: Vec<_> = learned.iter().map(|c| c.to_string()).collect();
+        slides.push(Slide {
+            instruction: "Mix them with the letters you already know"
+                .to_owned(),
+            line: SlideLine::Letters(mixed_groups(
+                &all,
+                GROUPS_PER_LINE,
+                GROUP_LENGTH,
+                0,
+            )),
+        });
+    }
+    slides.extend(word_slides(words, learned, new, WORD_SLIDES));
+
+    let mut persistent = new.to_vec();
+    persistent.push(" ".to_owned());
+    LessonConfig::new(
+        slides,
+        format!("Next you'll learn {}: {}", group, new.join(" ")),
+        persistent,
+    )
+}
+
+/// Lines of words that only use learned letters. If any letters are
+/// required, each word must also contain at least one of them.
+fn word_slides(
+    words: &[&str],
+    learned: &BTreeSet<char>,
+    required: &[String],
+    max_slides: usize,
+) -> Vec<Slide> {
+    let usable: Vec<&str> = words
+        .iter()
+        .cloned()
+        .filter(|word| word.chars().all(|c| learned.contains(&c)))
+        .filter(|word| {
+            required.is_empty()
+                || required.iter().any(|letter| word.contains(letter.as_str()))
+        })
+        .take(max_slides * WORDS_PER_LINE)
+        .collect();
+    usable
+        .chunks(WORDS_PER_LINE)
+        .map(|line| Slide {
+            instruction: "Type the words".to_owned(),
+            line: SlideLine::Letters(line.join(" ")),
+        })
+        .collect()
+}
+
+/// Make lessons that teach typing dictionary words with their own chords,
+/// then anagram modifiers, the other word modifiers, and the commands that fix
+/// mistyped words. Each word has to be typed with a single chord.
+pub fn generate_word_lessons(
+    data: &TutorData,
+    mode: &ModeName,
+) -> Vec<(String, LessonConfig)> {
+    let has_chord = |name: &Name| data.chord(name, mode).is_ok();
+    let space = data.word_space_position;
+    let (base, anagrams): (Vec<&Word>, Vec<&Word>) = data
+        .dictionary
+        .iter()
+        .filter(|word| word.word.chars().all(|c| c.is_ascii_graphic()))
+        .filter(|word| has_chord(&word.name()))
+        .partition(|word| word.anagram_num() == AnagramNum::default());
+    let pairs: Vec<(&Word, &Word)> = anagrams
+        .iter()
+        .filter_map(|anagram| {
+            let letters = chord_letters(anagram);
+            base.iter()
+                .find(|word| chord_letters(word) == letters)
+                .map(|word| (*word, *anagram))
+        })
+        .take(ANAGRAM_PAIRS)
+        .collect();
+    // Capitalizing words that are already capitalized wouldn't do anything.
+    let sample: Vec<&Word> = base
+        .iter()
+        .filter(|word| word.word.starts_with(char::is_lowercase))
+        .take(MODIFIER_WORDS)
+        .cloned()
+        .collect();
+
+    let mut lessons = Vec::new();
+    for (i, chunk) in base
+        .chunks(WORDS_PER_CHORD_LESSON)
+        .take(WORD_CHORD_LESSONS)
+        .enumerate()
+    {
+        let instruction = "Type each word with a single chord";
+        let mut slides: Vec<_> = chunk
+            .chunks(WORDS_PER_DRILL)
+            .map(|drill| {
+                let entries = drill
+                    .iter()
+                    .flat_map(|word| vec![word_entry(word, space); 2])
+                    .collect();
+                whole_word_slide(instruction, entries)
+            })
+            .collect();
+        let entries =
+            chunk.iter().map(|word| word_entry(word, space)).collect();
+        slides.push(whole_word_slide(instruction, entries));
+        lessons.push((
+            format!("{}) word chords part {}", lessons.len() + 1, i + 1),
+            LessonConfig::new(
+                slides,
+                "Each word in the dictionary has its own chord, made from the \
+                 chords of its letters. Press them all at once to type the \
+                 whole word."
+                    .to_owned(),
+                Vec::new(),
+            ),
+        ));
+    }
+
+    if !pairs.is_empty() {
+        let slides = pairs
+            .chunks(PAIRS_PER_LINE)
+            .map(|line| {
+                let entries = line
+                    .iter()
+                    .flat_map(|(word, anagram)| {
+                        vec![
+                            word_entry(word, space),
+                            word_entry(anagram, space),
+                            word_entry(word, space),
+                            word_entry(anagram, space),
+                        ]
+                    })
+                    .collect();
+                whole_word_slide(
+                    "Add the anagram modifier to type the other word",
+                    entries,
+                )
+            })
+            .collect();
+        lessons.push((
+            format!("{}) anagrams", lessons.len() + 1),
+            LessonConfig::new(
+                slides,
+                "Words made from the same letters share a chord. Add an \
+                 anagram modifier to type the other words."
+                    .to_owned(),
+                Vec::new(),
+            ),
+        ));
+    }
+
+    let capital = Name::from("mod_capital");
+    let nospace = Name::from("mod_nospace");
+    let mut slides = Vec::new();
+    if has_chord(&capital) {
+        let entries = sample
+            .iter()
+            .flat_map(|word| {
+                vec![
+                    word_entry(word, space),
+                    modified_entry(
+                        word,
+                        &capital,
+                        spaced(&capitalize(&word.word), space),
+                    ),
+                ]
+            })
+            .collect();
+        slides.push(whole_word_slide(
+            "Add the capital modifier to capitalize the word",
+            entries,
+        ));
+    }
+    let space_side = match space {
+        WordSpacePosition::Before => Some("before"),
+        WordSpacePosition::After => Some("after"),
+        // There's no space for the nospace modifier or command to leave out.
+        WordSpacePosition::None => None,
+    };
+    if let (true, Some(side)) = (has_chord(&nospace), space_side) {
+        let entries = sample
+            .iter()
+            .flat_map(|word| {
+                vec![
+                    word_entry(word, space),
+                    modified_entry(word, &nospace, word.word.clone()),
+                ]
+            })
+            .collect();
+        slides.push(whole_word_slide(
+            &format!(
+                "Add the nospace modifier to leave out the space {} the word",
+                side
+            ),
+            entries,
+        ));
+    }
+    if !slides.is_empty() {
+        lessons.push((
+            format!("{}) word modifiers", lessons.len() + 1),
+            LessonConfig::new(
+                slides,
+                "Modifiers change how a word is typed. Press them together \
+                 with the word's chord."
+                    .to_owned(),
+                Vec::new(),
+            ),
+        ));
+    }
+
+    let cycle_word = Name::from("command_cycle_word");
+    let cycle_capital = Name::from("command_cycle_capital");
+    let cycle_nospace = Name::from("command_cycle_nospace");
+    let mut slides = Vec::new();
+    if has_chord(&cycle_word) && !pairs.is_empty() {
+        let entries = pairs
+            .iter()
+            .take(MODIFIER_WORDS)
+            .flat_map(|(word, anagram)| {
+                vec![
+                    fixed_entry(word, spaced(&anagram.word, space)),
+                    command_entry(&cycle_word),
+                ]
+            })
+            .collect();
+        slides.push(whole_word_slide(
+            "Type the word without its anagram modifier, then cycle it",
+            entries,
+        ));
+    }
+    if has_chord(&cycle_capital) {
+        let entries = sample
+            .iter()
+            .flat_map(|word| {
+                vec![
+                    fixed_entry(word, spaced(&capitalize(&word.word), space)),
+                    command_entry(&cycle_capital),
+                ]
+            })
+            .collect();
+        slides.push(whole_word_slide(
+            "Type the word, then capitalize it",
+            entries,
+        ));
+    }
+    if let (true, Some(side)) = (has_chord(&cycle_nospace), space_side) {
+        let entries = sample
+            .iter()
+            .flat_map(|word| {
+                vec![
+                    fixed_entry(word, word.word.clone()),
+                    command_entry(&cycle_nospace),
+                ]
+            })
+            .collect();
+        slides.push(whole_word_slide(
+            &format!("Type the word, then remove the space {} it", side),
+            entries,
+        ));
+    }
+    if !slides.is_empty() {
+        lessons.push((
+            format!("{}) fixing words", lessons.len() + 1),
+            LessonConfig::new(
+                slides,
+                "If you typed a word wrong, you can fix it with a command \
+                 instead of deleting it."
+                    .to_owned(),
+                Vec::new(),
+            ),
+        ));
+    }
+    lessons
+}
+
+fn whole_word_slide(instruction: &str, words: Vec<SlideWord>) -> Slide {
+    Slide {
+        instruction: instruction.to_owned(),
+        line: SlideLine::Words {
+            words,
+            show_errors: true,
+            whole_words: true,
+        },
+    }
+}
+
+fn word_entry(word: &Word, space: WordSpacePosition) -> SlideWord {
+    fixed_entry(word, spaced(&word.word, space))
+}
+
+/// An entry for the word's chord, that should end up as different text after
+/// the following command.
+fn fixed_entry(word: &Word, text: String) -> SlideWord {
+    SlideWord {
+        names: vec![word.name()],
+        text,
+        length_override: None,
+    }
+}
+
+fn modified_entry(word: &Word, modifier: &Name, text: String) -> SlideWord {
+    SlideWord {
+        names: vec![word.name(), modifier.clone()],
+        text,
+        length_override: None,
+    }
+}
+
+fn command_entry(command: &Name) -> SlideWord {
+    SlideWord {
+        names: vec![command.clone()],
+        text: String::new(),
+        length_override: None,
+    }
+}
+
+/// The letters whose chords make up the word's chord.
+fn chord_letters(word: &Word) -> BTreeSet<char> {
+    word.chord_string().chars().collect()
+}
+
+/// The text typed by a word's chord, including the space that the firmware
+/// adds before or after it.
+fn spaced(word: &str, space: WordSpacePosition) -> String {
+    match space {
+        WordSpacePosition::Before => format!(" {}", word),
+        WordSpacePosition::After => format!("{} ", word),
+        WordSpacePosition::None => word.to_owned(),
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+impl ChordGroup {
+    fn new(letter: &str, chord: &Chord<KmapOrder>) -> Self {
+        if !letter.chars().all(char::is_alphabetic) {
+            return ChordGroup::Symbols;
+        }
+        let positions = get_switch_positions();
+        let fingers: Option<Vec<_>> = chord
+            .iter()
+            .enumerate()
+            .filter(|&(_, is_pressed)| is_pressed)
+            .map(|(i, _)| positions.get(i).map(|&pos| finger(pos)))
+            .collect();
+        let fingers = match fingers {
+            Some(fingers) => fingers,
+            None => return ChordGroup::Other,
+        };
+        match fingers.as_slice() {
+            [_] => ChordGroup::SingleSwitch,
+            [a, b] if a == b => ChordGroup::Finger(a.0, a.1),
+            [a, b] if a.0 == b.0 => ChordGroup::Hand(a.0),
+            [_, _] => ChordGroup::BothHands,
+            _ => ChordGroup::Other,
+        }
+    }
+}
+
+/// Which finger presses the switch at this position in the tutor's graphic.
+fn finger((x, y): (usize, usize)) -> (Hand, Finger) {
+    let hand = if x < MIDDLE_COLUMN {
+        Hand::Left
+    } else {
+        Hand::Right
+    };
+    if y >= THUMB_ROW {
+        return (hand, Finger::Thumb);
+    }
+    let column = match hand {
+        Hand::Left => x / COLUMN_WIDTH,
+        Hand::Right => RIGHT_COLUMN.saturating_sub(x) / COLUMN_WIDTH,
+    };
+    let finger = match column {
+        0 => Finger::Pinky,
+        1 => Finger::Ring,
+        2 => Finger::Middle,
+        _ => Finger::Index,
+    };
+    (hand, finger)
+}
+
+impl fmt::Display for ChordGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChordGroup::SingleSwitch => write!(f, "single switch chords"),
+            ChordGroup::Finger(hand, finger) => {
+                write!(f, "{} {} chords", hand, finger)
+            }
+            ChordGroup::Hand(hand) => write!(f, "{} hand chords", hand),
+            ChordGroup::BothHands => write!(f, "two hand chords"),
+            ChordGroup::Other => write!(f, "other chords"),
+            ChordGroup::Symbols => write!(f, "numbers and symbols"),
+        }
+    }
+}
+
+impl fmt::Display for Hand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Hand::Left => "left",
+            Hand::Right => "right",
+        };
+        f.write_str(s)
+    }
+}
+
+impl fmt::Display for Finger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Finger::Index => "index",
+            Finger::Middle => "middle",
+            Finger::Ring => "ring",
+            Finger::Pinky => "pinky",
+            Finger::Thumb => "thumb",
+        };
+        f.write_str(s)
+    }
+}
@@ -182,7 +182,7 @@ impl Switch {
     }
 }
 
-fn get_switch_positions() -> Vec<(usize, usize)> {
+pub fn get_switch_positions() -> Vec<(usize, usize)> {
     vec![
         (0, 2),
         (6, 1),
@@ -1,9 +1,11 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs::{self, File};
+use std::mem;
 use std::path::PathBuf;
 use std::time::Instant;
 
 use serde_yaml;
+use unicode_segmentation::UnicodeSegmentation;
 
 use cursive::event::{Callback, Event, EventResult, Key};
 use cursive::theme::ColorStyle;
@@ -16,9 +18,13 @@ use cursive::{Cursive, Printer};
 use error::{Error, ResultExt};
 
 use tutor::{
-    offset, Copier, Graphic, LabeledChord, PrevCharStatus, Slide, State,
+    offset, Copier, Graphic, LabeledChord, PrevCharStatus, Slide, SlideLine,
+    State, StatsRecorder,
 };
 
+/// Paragraphs of a text file longer than this are split into several lines.
+const MAX_TEXT_LINE_LENGTH: usize = 500;
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct LessonConfig {
@@ -31,6 +37,7 @@ pub struct LessonConfig {
 
 pub struct Lesson {
     pub popup: String,
+    name: String,
     slide_stack: Vec<Slide>,
     graphic: Graphic,
     copier: Copier,
@@ -39,6 +46,9 @@ pub struct Lesson {
     instruction: String,
     info_bar: String,
     total_slides: usize,
+    stats: StatsRecorder,
+    slide_start: Option<Instant>,
+    last_key: Option<Instant>,
 }
 
 impl LessonConfig {
@@ -74,6 +84,66 @@ impl LessonConfig {
         Ok(map)
     }
 
+    pub fn new(
+        slides: Vec<Slide>,
+        popup: String,
+        persistent: Vec<String>,
+    ) -> Self {
+        Self {
+            slides,
+            popup,
+            persistent,
+        }
+    }
+
+    /// A lesson for typing the text in any file, one paragraph at a time.
+    /// Characters that the current mode can't type are left out, and listed
+    /// in the popup.
+    pub fn from_text_file(path: &PathBuf) -> Result<LessonConfig, Error> {
+        let text = fs::read_to_string(path).with_context(|| {
+            format!("Failed to read text file: {}", path.display())
+        })?;
+        let (lines, skipped) = split_text(&text, State::can_type);
+        if lines.is_empty() {
+            return Err(Error::Empty(format!(
+                "typeable text in {}",
+                path.display()
+            )));
+        }
+        let popup = if skipped.is_empty() {
+            String::new()
+        } else {
+            let skipped: Vec<_> = skipped.into_iter().collect();
+            format!(
+                "These characters can't be typed in the current mode, so \
+                 they were left out: {}",
+                skipped.join(" ")
+            )
+        };
+        let slides = lines
+            .into_iter()
+            .map(|line| Slide {
+                instruction: String::new(),
+                line: SlideLine::Letters(line),
+            })
+            .collect();
+        Ok(Self::new(slides, popup, Vec::new()))
+    }
+
+    /// A lesson for drilling the chords the user is weakest on.
+    pub fn drill(slides: Vec<Slide>) -> Self {
+        Self {
+            slides,
+            popup: String::new(),
+            persistent: Vec::new(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn slides(&self) -> &[Slide] {
+        &self.slides
+    }
+
     fn name_from_path(path: &PathBuf) -> String {
         // TODO return result
         let s = path
@@ -89,7 +159,7 @@ impl LessonConfig {
 }
 
 impl Lesson {
-    pub fn new(config: LessonConfig) -> Result<Self, Error> {
+    pub fn new(name: &str, config: LessonConfig) -> Result<Self, Error> {
         let copier = Copier::new(79);
         let persistent = if State::show_persistent_letters() {
             config
@@ -106,6 +176,7 @@ impl Lesson {
         let mut lesson = Self {
             total_slides: slide_stack.len(),
             popup: config.popup,
+            name: name.to_owned(),
             graphic: Graphic::new(persistent),
             start_time: None,
             net_words: 0.,
@@ -113,6 +184,9 @@ impl Lesson {
             info_bar: String::new(),
             slide_stack,
             copier,
+            stats: StatsRecorder::default(),
+            slide_start: None,
+            last_key: None,
         };
         let status = lesson.next_slide().unwrap();
         lesson.update_chord(&status)?;
@@ -154,6 +228,26 @@ impl Lesson {
         }
     }
 
+    fn record_typed(&mut self) {
+        let now = Instant::now();
+        self.slide_start.get_or_insert(now);
+        if let Some((typed, was_correct)) = self.copier.take_typed() {
+            let elapsed = self.last_key.map(|last| now - last);
+            self.last_key = Some(now);
+            self.stats.record_char(&typed, was_correct, elapsed);
+        }
+    }
+
+    fn end_slide_stats(&mut self, net_words: f64) {
+        let elapsed = self
+            .slide_start
+            .take()
+            .map(|start| start.elapsed())
+            .unwrap_or_default();
+        self.last_key = None;
+        self.stats.end_slide(net_words, elapsed);
+    }
+
     fn minutes(&self) -> f64 {
         (self
             .start_time
@@ -298,16 +392,25 @@ impl View for Lesson {
             Event::Key(Key::Backspace) => self.copier.type_backspace(),
             Event::Char(letter) => {
                 self.start_if_not_started();
-                self.copier.type_char(letter)
+                let status = self.copier.type_char(letter);
+                self.record_typed();
+                status
             }
             Event::CtrlChar('j') | Event::Key(Key::Enter)
                 if self.copier.at_end_of_line() =>
             {
                 // End the line, and maybe the whole lesson.
-                self.net_words += self.copier.net_words();
+                let net_words = self.copier.net_words();
+                self.net_words += net_words;
+                self.end_slide_stats(net_words);
+                State::save_reviews();
                 if self.slide_stack.is_empty() {
                     // Lesson is done
                     let wpm = self.words_per_minute();
+                    State::record_lesson(
+                        &self.name,
+                        mem::take(&mut self.stats),
+                    );
                     return EventResult::Consumed(Some(Callback::from_fn(
                         move |siv| end_lesson_callback(siv, wpm),
                     )));
@@ -324,6 +427,60 @@ impl View for Lesson {
     }
 }
 
+/// Split text into lines to type, one for each paragraph, with whitespace
+/// collapsed to single spaces. Also return the characters that were left out
+/// because they can't be typed.
+pub fn split_text<F>(text: &str, can_type: F) -> (Vec<String>, BTreeSet<String>)
+where
+    F: Fn(&str) -> bool,
+{
+    // Paragraphs are separated by blank lines.
+    let mut paragraphs = vec![Vec::new()];
+    for text_line in text.lines() {
+        if text_line.trim().is_empty() {
+            paragraphs.push(Vec::new());
+        } else {
+            paragraphs.last_mut().unwrap().push(text_line);
+        }
+    }
+
+    let mut lines = Vec::new();
+    let mut skipped = BTreeSet::new();
+    for paragraph in paragraphs {
+        let mut line = String::new();
+        let mut line_length = 0;
+        for word in paragraph.iter().flat_map(|l| l.split_whitespace()) {
+            let mut typeable = String::new();
+            for letter in word.graphemes(true) {
+                if can_type(letter) {
+                    typeable += letter;
+                } else {
+                    skipped.insert(letter.to_owned());
+                }
+            }
+            let length = typeable.graphemes(true).count();
+            if length == 0 {
+                continue;
+            }
+            if line_length > 0 {
+                if line_length + 1 + length > MAX_TEXT_LINE_LENGTH {
+                    lines.push(mem::take(&mut line));
+                    line_length = 0;
+                } else {
+                    line.push(' ');
+                    line_length += 1;
+                }
+            }
+            line += &typeable;
+            line_length += length;
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+    }
+    (lines, skipped)
+}
+
 fn end_lesson_callback(siv: &mut Cursive, wpm: usize) {
     let message = format!("Lesson complete.\nNet WPM: {}", wpm);
     siv.add_layer(Dialog::around(TextView::new(message)).button(
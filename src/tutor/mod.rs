@@ -1,17 +1,29 @@
+#[cfg(test)]
+pub(crate) use self::copier::Copier;
 use self::copier::*;
+pub(crate) use self::generator::{generate_lessons, generate_word_lessons};
 use self::graphic::*;
 use self::label::*;
+#[cfg(test)]
+pub(crate) use self::lesson::split_text;
 use self::lesson::*;
+pub(crate) use self::review::{now, ReviewSchedule};
+#[cfg(test)]
+pub(crate) use self::slide::SlideLine;
 use self::slide::*;
 use self::state::*;
+pub(crate) use self::stats::*;
 pub use self::tutor_app::TutorApp;
 use self::tutor_util::*;
 
 mod copier;
+mod generator;
 mod graphic;
 mod label;
 mod lesson;
+mod review;
 mod slide;
 mod state;
+mod stats;
 pub mod tutor_app;
 mod tutor_util;
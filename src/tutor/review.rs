@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tutor::{mixed_groups, Slide, SlideLine};
+
+/// How long to wait before reviewing a chord that was just typed wrong, in
+/// seconds.
+const INITIAL_INTERVAL: u64 = 60;
+
+const INITIAL_EASE: f64 = 2.5;
+const MIN_EASE: f64 = 1.3;
+const EASE_PENALTY: f64 = 0.2;
+const EASE_BONUS: f64 = 0.05;
+
+/// The number of chords to practice in a single drill.
+const DRILL_LETTERS: usize = 6;
+const DRILL_SLIDES: usize = 4;
+const GROUPS_PER_LINE: usize = 8;
+const GROUP_LENGTH: usize = 4;
+
+/// When each chord should next be reviewed, based on how well the user has
+/// typed it before.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReviewSchedule(BTreeMap<String, ReviewState>);
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReviewState {
+    /// How quickly the interval grows after each successful review.
+    pub ease: f64,
+    /// The number of seconds between reviews.
+    pub interval: u64,
+    /// When the chord was last reviewed, in seconds since the unix epoch.
+    pub last_seen: u64,
+    pub errors: usize,
+}
+
+impl ReviewSchedule {
+    /// Record an attempt to type the chord for the letter. Mistakes always
+    /// shorten the interval, but correct answers only lengthen it once the
+    /// chord is due, so typing it many times in a row doesn't count.
+    pub fn record(&mut self, letter: &str, was_correct: bool, now: u64) {
+        let state = self
+            .0
+            .entry(letter.to_owned())
+            .or_insert_with(|| ReviewState::new(now));
+        if !was_correct {
+            state.errors += 1;
+            state.ease = (state.ease - EASE_PENALTY).max(MIN_EASE);
+            state.interval = INITIAL_INTERVAL;
+            state.last_seen = now;
+        } else if state.is_due(now) {
+            state.ease += EASE_BONUS;
+            state.interval = (state.interval as f64 * state.ease) as u64;
+            state.last_seen = now;
+        }
+    }
+
+    #[cfg(test)]
+    pub fn get(&self, letter: &str) -> Option<&ReviewState> {
+        self.0.get(letter)
+    }
+
+    /// The letters that most need practice, starting with the ones that are
+    /// due and have the lowest ease.
+    pub fn weakest(&self, max: usize, now: u64) -> Vec<String> {
+        let mut letters: Vec<_> = self
+            .0
+            .iter()
+            .filter(|(letter, _)| !letter.trim().is_empty())
+            .collect();
+        letters.sort_by(|(_, a), (_, b)| {
+            (!a.is_due(now), b.errors)
+                .cmp(&(!b.is_due(now), a.errors))
+                .then(a.ease.partial_cmp(&b.ease).expect("ease is NaN"))
+        });
+        letters
+            .into_iter()
+            .take(max)
+            .map(|(letter, _)| letter.to_owned())
+            .collect()
+    }
+
+    /// Make slides that mix together the letters that most need practice.
+    /// Returns an empty list if no letters have been typed yet.
+    pub fn drill_slides(&self, now: u64) -> Vec<Slide> {
+        let letters = self.weakest(DRILL_LETTERS, now);
+        if letters.is_empty() {
+            return Vec::new();
+        }
+        (0..DRILL_SLIDES)
+            .map(|slide| Slide {
+                instruction: "Practice the chords you're weakest on".to_owned(),
+                line: SlideLine::Letters(mixed_groups(
+                    &letters,
+                    GROUPS_PER_LINE,
+                    GROUP_LENGTH,
+                    slide,
+                )),
+            })
+            .collect()
+    }
+}
+
+impl ReviewState {
+    fn new(now: u64) -> Self {
+        Self {
+            ease: INITIAL_EASE,
+            interval: INITIAL_INTERVAL,
+            last_seen: now,
+            errors: 0,
+        }
+    }
+
+    pub fn is_due(&self, now: u64) -> bool {
+        now >= self.last_seen + self.interval
+    }
+}
+
+/// The current time, in seconds since the unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
@@ -3,7 +3,7 @@ use std::fmt;
 use unicode_segmentation::UnicodeSegmentation;
 
 use error::{Error, ResultExt};
-use tutor::{Label, LabeledChord, State};
+use tutor::{Label, LabeledChord};
 use types::{Chord, KmapOrder, Name};
 
 #[derive(Debug, Clone, Deserialize)]
@@ -22,6 +22,11 @@ pub enum SlideLine {
         words: Vec<SlideWord>,
         #[serde(default = "return_true")]
         show_errors: bool,
+        /// Expect each word to be typed with its own chord, instead of letter
+        /// by letter. Words with empty text are commands that fix the
+        /// previous word, and are only hinted if it's typed wrong.
+        #[serde(default)]
+        whole_words: bool,
     },
 }
 
@@ -50,6 +55,13 @@ impl SlideLine {
         }
     }
 
+    pub fn whole_words(&self) -> bool {
+        match self {
+            SlideLine::Letters(_) => false,
+            SlideLine::Words { whole_words, .. } => *whole_words,
+        }
+    }
+
     pub fn has_length_overrides(&self) -> bool {
         match self {
             SlideLine::Letters(_) => false,
@@ -59,14 +71,22 @@ impl SlideLine {
         }
     }
 
-    pub fn to_entries(&self) -> Result<(Vec<SlideEntry>, String), Error> {
+    /// Get each word's chord and text, using the given function to look up
+    /// chords by name.
+    pub fn to_entries<F>(
+        &self,
+        chord: F,
+    ) -> Result<(Vec<SlideEntry>, String), Error>
+    where
+        F: Fn(&Name) -> Result<Chord<KmapOrder>, Error>,
+    {
         Ok(match self {
             SlideLine::Letters(string) => (Vec::new(), string.to_owned()),
             SlideLine::Words { words, .. } => {
                 let entries: Result<Vec<_>, _> = words
                     .iter()
                     .map(|word| {
-                        SlideEntry::from_word(word).with_context(|| {
+                        SlideEntry::from_word(word, &chord).with_context(|| {
                             format!(
                                 "Failed to make slide entry from word: {}",
                                 word
@@ -95,11 +115,14 @@ impl SlideEntry {
     //     })
     // }
 
-    fn from_word(word: &SlideWord) -> Result<Self, Error> {
+    fn from_word<F>(word: &SlideWord, chord: F) -> Result<Self, Error>
+    where
+        F: Fn(&Name) -> Result<Chord<KmapOrder>, Error>,
+    {
         let chords = word
             .names
             .iter()
-            .map(|name| State::chord(name))
+            .map(chord)
             .collect::<Result<Vec<_>, _>>()?;
 
         let chord = chords
@@ -1,5 +1,5 @@
 use serde_yaml;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::fs::OpenOptions;
 use std::io::Write;
@@ -7,7 +7,13 @@ use std::path::PathBuf;
 use std::sync::Mutex;
 
 use error::{Error, ResultExt};
-use types::{Chord, KmapOrder, ModeName, Name, Spelling, TutorData};
+use tutor::{
+    generate_lessons, generate_word_lessons, now, LessonConfig, LessonHistory,
+    ReviewSchedule, Slide, StatsRecorder,
+};
+use types::{
+    Chord, KmapOrder, ModeName, Name, SourceSpan, Spelling, TutorData,
+};
 
 lazy_static! {
     static ref STATE: Mutex<Option<InnerState>> = Mutex::new(None);
@@ -19,6 +25,8 @@ pub struct InnerState {
     learning_map: HashMap<String, LearnState>,
     save_path: PathBuf,
     saveable: SaveableSettings,
+    history: LessonHistory,
+    history_path: PathBuf,
 }
 
 // TODO module instead?
@@ -32,6 +40,9 @@ pub struct SaveableSettings {
     mode: ModeName,
     allow_mistakes: bool,
     show_persistent_letters: bool,
+    /// Each mode has different chords, so they're reviewed separately.
+    #[serde(default)]
+    reviews: BTreeMap<ModeName, ReviewSchedule>,
 }
 
 #[derive(Debug, Clone)]
@@ -80,6 +91,12 @@ impl InnerState {
     }
 
     fn update_learn_state(&mut self, name: String, was_correct: bool) {
+        self.saveable
+            .reviews
+            .entry(self.saveable.mode.clone())
+            .or_default()
+            .record(&name, was_correct, now());
+
         let initial_learn_state = self.saveable.initial_learn_state;
         self.learning_map
             .entry(name)
@@ -87,6 +104,21 @@ impl InnerState {
             .update(was_correct);
     }
 
+    fn drill_slides(&self) -> Vec<Slide> {
+        self.saveable
+            .reviews
+            .get(&self.saveable.mode)
+            .map(|reviews| reviews.drill_slides(now()))
+            .unwrap_or_default()
+    }
+
+    fn record_lesson(&mut self, lesson: &str, stats: StatsRecorder) {
+        let stats = stats.finish(lesson, self.saveable.mode.clone(), now());
+        self.history.add(stats);
+        // ignore any errors while saving
+        self.history.save(&self.history_path).ok();
+    }
+
     fn set_initial_learn_state(&mut self, initial: usize) {
         for learn_state in self.learning_map.values_mut() {
             learn_state.reset(initial);
@@ -150,11 +182,15 @@ impl State {
         let save_path = PathBuf::from("settings/tutor/saved_options.yaml");
         let saveable = SaveableSettings::from_file(&save_path, &tutor_data)?
             .unwrap_or_default();
+        let history_path = PathBuf::from("settings/tutor/history.yaml");
+        let history = LessonHistory::load(&history_path);
         let inner = InnerState {
             tutor_data,
             learning_map: HashMap::new(),
             save_path,
             saveable,
+            history,
+            history_path,
         };
 
         *state = Some(inner);
@@ -197,6 +233,70 @@ impl State {
             .update_learn_state(name, was_correct);
     }
 
+    pub fn drill_slides() -> Vec<Slide> {
+        STATE
+            .lock()
+            .unwrap()
+            .as_ref()
+            .expect("state not set")
+            .drill_slides()
+    }
+
+    /// Save the review schedule along with the other settings. The schedule
+    /// changes with every letter typed, so it's only saved after each line.
+    pub fn save_reviews() {
+        // ignore any errors while saving
+        STATE
+            .lock()
+            .unwrap()
+            .as_ref()
+            .expect("state not set")
+            .save_settings()
+            .ok();
+    }
+
+    /// Save the stats for a lesson that was just finished.
+    pub fn record_lesson(lesson: &str, stats: StatsRecorder) {
+        STATE
+            .lock()
+            .unwrap()
+            .as_mut()
+            .expect("state not set")
+            .record_lesson(lesson, stats)
+    }
+
+    /// Lessons for the letters in the current mode.
+    pub fn generate_lessons() -> Vec<(String, LessonConfig)> {
+        let state = STATE.lock().unwrap();
+        let state = state.as_ref().expect("state not set");
+        generate_lessons(&state.tutor_data, &state.saveable.mode)
+    }
+
+    /// Lessons for the word chords in the current mode.
+    pub fn generate_word_lessons() -> Vec<(String, LessonConfig)> {
+        let state = STATE.lock().unwrap();
+        let state = state.as_ref().expect("state not set");
+        generate_word_lessons(&state.tutor_data, &state.saveable.mode)
+    }
+
+    /// Whether the letter has a chord in the current mode.
+    pub fn can_type(letter: &str) -> bool {
+        Spelling::new(letter)
+            .ok()
+            .and_then(Self::chord_from_spelling)
+            .is_some()
+    }
+
+    pub fn history() -> LessonHistory {
+        STATE
+            .lock()
+            .unwrap()
+            .as_ref()
+            .expect("state not set")
+            .history
+            .clone()
+    }
+
     pub fn initial_learn_state() -> usize {
         STATE
             .lock()
@@ -292,6 +392,7 @@ impl Default for SaveableSettings {
             mode: ModeName::default(),
             allow_mistakes: false,
             show_persistent_letters: true,
+            reviews: BTreeMap::new(),
         }
     }
 }
@@ -348,6 +449,11 @@ impl TutorData {
                 container: "tutor data chords".to_owned(),
             })
     }
+
+    /// Where the chord was defined in a kmap file, if it was.
+    pub fn span(&self, name: &Name, mode: &ModeName) -> Option<&SourceSpan> {
+        self.spans.get(mode)?.get(name)
+    }
 }
 
 impl LearnState {
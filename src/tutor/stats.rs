@@ -0,0 +1,285 @@
+use serde_yaml;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::mem;
+use std::path::PathBuf;
+use std::time::Duration;
+use time;
+
+use error::{Error, ResultExt};
+use types::ModeName;
+
+/// The number of chords to show in the details of a lesson's stats.
+const SLOWEST_CHORDS: usize = 8;
+
+/// The number of completions of each lesson to keep, so that the history
+/// doesn't grow forever.
+const MAX_COMPLETIONS: usize = 20;
+
+/// The most recent completions of every lesson the user has finished, oldest
+/// first.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LessonHistory(Vec<LessonStats>);
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LessonStats {
+    pub lesson: String,
+    pub mode: ModeName,
+    /// When the lesson was finished, in seconds since the unix epoch.
+    pub completed: u64,
+    pub slides: Vec<SlideStats>,
+    pub chars: BTreeMap<String, CharStats>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SlideStats {
+    pub typed: usize,
+    pub errors: usize,
+    /// The time from the first keypress to the end of the line.
+    pub millis: u64,
+    /// The number of words typed, minus one for each mistake.
+    pub net_words: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CharStats {
+    pub typed: usize,
+    pub errors: usize,
+    /// The total time spent on the keypresses that were timed. The first
+    /// keypress of each line isn't, since there's nothing to time it from.
+    pub millis: u64,
+    pub timed: usize,
+}
+
+/// Collects stats while a lesson is being typed.
+#[derive(Debug, Clone, Default)]
+pub struct StatsRecorder {
+    slides: Vec<SlideStats>,
+    current: SlideStats,
+    chars: BTreeMap<String, CharStats>,
+}
+
+impl LessonHistory {
+    pub fn from_file(path: &PathBuf) -> Result<Self, Error> {
+        if let Ok(file) = File::open(path) {
+            serde_yaml::from_reader(file).with_context(|| {
+                format!("failed to read file: {}", path.display())
+            })
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Load the history, or start a new one if it can't be read. An old
+    /// history that can't be read is moved out of the way instead of being
+    /// overwritten.
+    pub fn load(path: &PathBuf) -> Self {
+        Self::from_file(path).unwrap_or_else(|error| {
+            let old_path = path.with_extension("yaml.old");
+            eprintln!("{}", error);
+            match fs::rename(path, &old_path) {
+                Ok(()) => eprintln!(
+                    "Starting a new lesson history. The old one was moved to: \
+                     {}",
+                    old_path.display()
+                ),
+                Err(_) => eprintln!("Starting a new lesson history."),
+            }
+            Self::default()
+        })
+    }
+
+    pub fn save(&self, path: &PathBuf) -> Result<(), Error> {
+        let s = serde_yaml::to_string(self)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)
+            .context("Failed to open output file")?;
+        file.write_all(s.as_bytes())
+            .context("Failed to write to output file")?;
+        Ok(())
+    }
+
+    /// Add a completion, forgetting the oldest completions of the same lesson
+    /// if there are too many.
+    pub fn add(&mut self, stats: LessonStats) {
+        let lesson = stats.lesson.clone();
+        self.0.push(stats);
+        let completions =
+            self.0.iter().filter(|stats| stats.lesson == lesson).count();
+        for _ in MAX_COMPLETIONS..completions {
+            let oldest = self
+                .0
+                .iter()
+                .position(|stats| stats.lesson == lesson)
+                .expect("lesson was just added");
+            self.0.remove(oldest);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Each lesson's completions, oldest first.
+    pub fn by_lesson(&self) -> BTreeMap<String, Vec<LessonStats>> {
+        let mut map: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for stats in &self.0 {
+            map.entry(stats.lesson.clone())
+                .or_default()
+                .push(stats.clone());
+        }
+        map
+    }
+}
+
+impl LessonStats {
+    /// The stats for all slides together.
+    pub fn total(&self) -> SlideStats {
+        let mut total = SlideStats::default();
+        for slide in &self.slides {
+            total.typed += slide.typed;
+            total.errors += slide.errors;
+            total.millis += slide.millis;
+            total.net_words += slide.net_words;
+        }
+        total
+    }
+
+    /// The chords that took the longest to type on average, slowest first.
+    pub fn slowest(&self, max: usize) -> Vec<(&str, u64)> {
+        let mut chars: Vec<_> = self
+            .chars
+            .iter()
+            .filter(|(letter, _)| !letter.trim().is_empty())
+            .filter_map(|(letter, stats)| {
+                Some((letter.as_str(), stats.average_millis()?))
+            })
+            .collect();
+        chars.sort_by(|(_, a), (_, b)| b.cmp(a));
+        chars.truncate(max);
+        chars
+    }
+
+    pub fn date(&self) -> String {
+        let timespec = time::Timespec::new(self.completed as i64, 0);
+        time::at(timespec)
+            .strftime("%Y-%m-%d %H:%M")
+            .map(|date| date.to_string())
+            .unwrap_or_default()
+    }
+
+    /// A one line summary, for listing many completions together.
+    pub fn summary(&self) -> String {
+        let total = self.total();
+        format!(
+            "{}  {:>3} WPM  {:>3.0}% accuracy  ({})",
+            self.date(),
+            total.words_per_minute(),
+            total.accuracy() * 100.,
+            self.mode
+        )
+    }
+
+    /// The speed of each line and the slowest chords.
+    pub fn details(&self) -> String {
+        let slides: Vec<_> = self
+            .slides
+            .iter()
+            .map(|slide| slide.words_per_minute().to_string())
+            .collect();
+        let slowest: Vec<_> = self
+            .slowest(SLOWEST_CHORDS)
+            .into_iter()
+            .map(|(letter, millis)| format!("{} {}ms", letter, millis))
+            .collect();
+        format!(
+            "WPM per line: {}\nSlowest chords: {}",
+            slides.join(", "),
+            slowest.join(", ")
+        )
+    }
+}
+
+impl SlideStats {
+    pub fn words_per_minute(&self) -> usize {
+        if self.millis == 0 {
+            return 0;
+        }
+        (self.net_words / (self.millis as f64 / 60_000.)) as usize
+    }
+
+    /// The fraction of keypresses that were correct.
+    pub fn accuracy(&self) -> f64 {
+        if self.typed == 0 {
+            return 1.;
+        }
+        1. - self.errors as f64 / self.typed as f64
+    }
+}
+
+impl CharStats {
+    pub fn average_millis(&self) -> Option<u64> {
+        if self.timed == 0 {
+            None
+        } else {
+            Some(self.millis / self.timed as u64)
+        }
+    }
+}
+
+impl StatsRecorder {
+    /// Record typing a letter or a whole word, and how long it took since the
+    /// previous one, if it's known.
+    pub fn record_char(
+        &mut self,
+        letter: &str,
+        was_correct: bool,
+        elapsed: Option<Duration>,
+    ) {
+        let stats = self.chars.entry(letter.to_owned()).or_default();
+        stats.typed += 1;
+        self.current.typed += 1;
+        if !was_correct {
+            stats.errors += 1;
+            self.current.errors += 1;
+        }
+        if let Some(elapsed) = elapsed {
+            stats.millis += millis(elapsed);
+            stats.timed += 1;
+        }
+    }
+
+    pub fn end_slide(&mut self, net_words: f64, elapsed: Duration) {
+        let mut slide = mem::take(&mut self.current);
+        slide.millis = millis(elapsed);
+        slide.net_words = net_words;
+        self.slides.push(slide);
+    }
+
+    pub fn finish(
+        self,
+        lesson: &str,
+        mode: ModeName,
+        completed: u64,
+    ) -> LessonStats {
+        LessonStats {
+            lesson: lesson.to_owned(),
+            mode,
+            completed,
+            slides: self.slides,
+            chars: self.chars,
+        }
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_millis())
+}
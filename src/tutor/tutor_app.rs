@@ -1,15 +1,18 @@
 use natord;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 
 use cursive::align::HAlign;
 use cursive::direction::Orientation;
+use cursive::traits::*;
 use cursive::views::{
-    Checkbox, Dialog, ListView, SelectView, SliderView, TextView,
+    Checkbox, Dialog, EditView, ListView, SelectView, SliderView, TextView,
 };
 use cursive::Cursive;
 
 use types::TutorData;
 
-use tutor::{Lesson, LessonConfig, State};
+use tutor::{Lesson, LessonConfig, LessonStats, State};
 
 pub struct TutorApp;
 
@@ -27,12 +30,26 @@ impl TutorApp {
     }
 
     fn show_main_menu(siv: &mut Cursive) {
-        let items = vec!["Lessons", "Options", "Quit"];
+        let items = vec![
+            "Lessons",
+            "Generated lessons",
+            "Word chord lessons",
+            "Practice a text file",
+            "Review",
+            "Progress",
+            "Options",
+            "Quit",
+        ];
         let select = SelectView::new()
             .h_align(HAlign::Left)
             .with_all_str(items)
             .on_submit(move |siv, item| match item {
                 "Lessons" => Self::show_lesson_menu(siv),
+                "Generated lessons" => Self::show_generated_lesson_menu(siv),
+                "Word chord lessons" => Self::show_word_lesson_menu(siv),
+                "Practice a text file" => Self::show_text_file_prompt(siv),
+                "Review" => Self::show_review(siv),
+                "Progress" => Self::show_progress(siv),
                 "Options" => Self::show_option_menu(siv),
                 "Quit" => siv.quit(),
                 _ => panic!("unknown menu item"),
@@ -90,7 +107,52 @@ impl TutorApp {
         // TODO don't hardcode
         let lessons = LessonConfig::load_directory("settings/tutor/lessons/")
             .expect("failed to get lessons");
+        Self::show_lesson_list(siv, "Lessons", lessons);
+    }
+
+    /// Show lessons made from the chords in the current mode, instead of the
+    /// hand-written ones.
+    fn show_generated_lesson_menu(siv: &mut Cursive) {
+        let lessons = State::generate_lessons().into_iter().collect();
+        Self::show_lesson_list(siv, "Generated lessons", lessons);
+    }
+
+    /// Show lessons for typing whole words with their chords.
+    fn show_word_lesson_menu(siv: &mut Cursive) {
+        let lessons = State::generate_word_lessons().into_iter().collect();
+        Self::show_lesson_list(siv, "Word chord lessons", lessons);
+    }
+
+    fn show_text_file_prompt(siv: &mut Cursive) {
+        let path = EditView::new()
+            .on_submit(Self::show_text_file)
+            .fixed_width(60);
+        siv.add_layer(
+            Dialog::around(path)
+                .title("Path to a text file")
+                .dismiss_button("Back"),
+        );
+    }
+
+    fn show_text_file(siv: &mut Cursive, path: &str) {
+        match LessonConfig::from_text_file(&PathBuf::from(path)) {
+            Ok(lesson) => {
+                siv.pop_layer(); // pop prompt
+                Self::show_lesson(siv, path, lesson);
+            }
+            Err(err) => siv.add_layer(
+                Dialog::around(TextView::new(err.to_string()))
+                    .title("Failed to load text file")
+                    .dismiss_button("Back"),
+            ),
+        }
+    }
 
+    fn show_lesson_list(
+        siv: &mut Cursive,
+        title: &str,
+        lessons: BTreeMap<String, LessonConfig>,
+    ) {
         let mut names: Vec<String> = lessons.keys().cloned().collect();
         names.sort_by(|a, b| natord::compare(a, b));
 
@@ -102,7 +164,7 @@ impl TutorApp {
             Self::show_lesson(siv, name, lesson)
         });
 
-        siv.add_layer(Dialog::around(select).title("Lessons").button(
+        siv.add_layer(Dialog::around(select).title(title).button(
             "Back",
             |siv| {
                 siv.pop_layer().expect("No layer to pop!");
@@ -110,13 +172,83 @@ impl TutorApp {
         ));
     }
 
-    fn show_lesson(
+    fn show_review(siv: &mut Cursive) {
+        let slides = State::drill_slides();
+        if slides.is_empty() {
+            siv.add_layer(
+                Dialog::around(TextView::new(
+                    "Nothing to review yet. Try some lessons first!",
+                ))
+                .dismiss_button("Back"),
+            );
+            return;
+        }
+        Self::show_lesson(siv, "Review", LessonConfig::drill(slides));
+    }
+
+    fn show_progress(siv: &mut Cursive) {
+        let history = State::history();
+        if history.is_empty() {
+            siv.add_layer(
+                Dialog::around(TextView::new(
+                    "No lessons finished yet. Try some lessons first!",
+                ))
+                .dismiss_button("Back"),
+            );
+            return;
+        }
+
+        let mut lessons: Vec<_> = history.by_lesson().into_iter().collect();
+        lessons.sort_by(|(a, _), (b, _)| natord::compare(a, b));
+
+        let mut select = SelectView::new().h_align(HAlign::Left);
+        for (name, completions) in lessons {
+            let best = completions
+                .iter()
+                .map(|stats| stats.total().words_per_minute())
+                .max()
+                .unwrap_or(0);
+            let label = format!(
+                "{}  ({} recent completions, best {} WPM)",
+                name,
+                completions.len(),
+                best
+            );
+            select.add_item(label, (name, completions));
+        }
+        select.set_on_submit(|siv, (name, completions)| {
+            Self::show_lesson_progress(siv, name, completions)
+        });
+
+        siv.add_layer(Dialog::around(select).title("Progress").button(
+            "Back",
+            |siv| {
+                siv.pop_layer().expect("No layer to pop!");
+            },
+        ));
+    }
+
+    fn show_lesson_progress(
         siv: &mut Cursive,
-        _name: &str,
-        lesson_config: LessonConfig,
+        name: &str,
+        completions: &[LessonStats],
     ) {
+        let mut lines: Vec<_> =
+            completions.iter().map(|stats| stats.summary()).collect();
+        if let Some(last) = completions.last() {
+            lines.push(String::new());
+            lines.push(format!("Last time:\n{}", last.details()));
+        }
+        siv.add_layer(
+            Dialog::around(TextView::new(lines.join("\n")))
+                .title(name)
+                .dismiss_button("Back"),
+        );
+    }
+
+    fn show_lesson(siv: &mut Cursive, name: &str, lesson_config: LessonConfig) {
         // TODO will unwrap print nicely?
-        let lesson = Lesson::new(lesson_config).unwrap();
+        let lesson = Lesson::new(name, lesson_config).unwrap();
         let popup = lesson.popup.clone();
         siv.add_layer(lesson);
         if !popup.is_empty() {
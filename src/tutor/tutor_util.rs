@@ -1,3 +1,30 @@
 pub fn offset(width1: usize, width2: usize) -> usize {
     ((width2 - width1) as f32 / 2.).round() as usize
 }
+
+/// Mix the letters together into space separated groups, for drilling them.
+/// Every letter is used once before any of them repeat, so they all appear
+/// as long as there's room. Different seeds give different orders.
+pub fn mixed_groups(
+    letters: &[String],
+    num_groups: usize,
+    group_length: usize,
+    seed: usize,
+) -> String {
+    // A simple linear congruential generator is random enough to keep the
+    // groups from repeating, and always gives the same lessons.
+    let mut state = seed as u32;
+    let mut unused: Vec<&str> = Vec::new();
+    let mut next_letter = || {
+        if unused.is_empty() {
+            unused = letters.iter().map(|letter| letter.as_str()).collect();
+        }
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        let index = (state >> 16) as usize % unused.len();
+        unused.swap_remove(index)
+    };
+    let groups: Vec<String> = (0..num_groups)
+        .map(|_| (0..group_length).map(|_| next_letter()).collect())
+        .collect();
+    groups.join(" ")
+}
@@ -5,14 +5,19 @@ use std::path::PathBuf;
 
 use error::{Error, ResultExt};
 use types::{
-    AnagramNum, BoardName, Chord, ChordSpec, Command, HuffmanTable, KeyPress,
-    KmapOrder, KmapPath, ModeInfo, ModeName, Name, SeqType, Sequence,
-    SpellingTable, TutorData, UserOptions,
+    AnagramNum, Chord, ChordSpec, Command, KeyPress, KmapOrder, KmapPath,
+    ModeInfo, ModeName, Name, SeqType, Sequence, SequenceEncoder, SourceSpan,
+    SpellingTable, TutorData, UserOptions, Word,
 };
 use util::ensure_u8;
 
 #[derive(Debug, Default)]
-pub struct ChordMap(BTreeMap<Name, Chord<KmapOrder>>);
+pub struct ChordMap {
+    chords: BTreeMap<Name, Chord<KmapOrder>>,
+    /// Where each chord was defined, if it came from a kmap file instead of
+    /// being generated.
+    spans: BTreeMap<Name, SourceSpan>,
+}
 
 #[derive(Debug, Default)]
 pub struct SeqMap(BTreeMap<Name, Sequence>);
@@ -31,17 +36,17 @@ pub struct AllChordMaps {
 pub struct AllData {
     pub chords: AllChordMaps,
     pub sequences: AllSeqMaps,
+    pub dictionary: Vec<Word>,
     pub word_mods: Vec<Name>,
     pub plain_mods: Vec<Name>,
     pub anagram_mods: Vec<Name>,
     pub modes: BTreeMap<ModeName, ModeInfo>,
-    pub huffman_table: HuffmanTable,
+    pub encoder: SequenceEncoder,
     pub spellings: SpellingTable,
     pub commands: Vec<Command>,
     pub user_options: UserOptions,
     pub output_directory: PathBuf,
     pub chord_spec: ChordSpec,
-    pub board: BoardName,
 }
 
 impl AllData {
@@ -60,6 +65,23 @@ impl AllData {
         None
     }
 
+    /// Return where the first chord found for the given name in any of this
+    /// mode's kmaps was defined, if it came from a kmap file.
+    pub fn get_span_in_mode(
+        &self,
+        chord_name: &Name,
+        mode: &ModeName,
+    ) -> Option<&SourceSpan> {
+        let kmap_info = self
+            .modes
+            .get(mode)
+            .expect("unknown mode")
+            .keymaps
+            .iter()
+            .find(|info| self.chords.get(chord_name, &info.file).is_ok())?;
+        self.chords.span(chord_name, &kmap_info.file)
+    }
+
     /// Get a Chord containing all the switches used in any anagram mod.
     pub fn get_anagram_mask(&self, mode: &ModeName) -> Chord<KmapOrder> {
         self.anagram_mods
@@ -69,6 +91,24 @@ impl AllData {
             .unwrap_or_else(|| self.chord_spec.new_chord())
     }
 
+    /// Return all the kmaps that contain chords for the dictionary words.
+    pub fn kmaps_with_words(&self) -> BTreeSet<&KmapPath> {
+        self.modes
+            .values()
+            .flat_map(|mode_info| mode_info.keymaps.iter())
+            .filter(|kmap_info| kmap_info.use_words)
+            .map(|kmap_info| &kmap_info.file)
+            .collect()
+    }
+
+    /// Return all the kmaps used by any mode.
+    pub fn kmap_paths(&self) -> BTreeSet<&KmapPath> {
+        self.modes
+            .values()
+            .flat_map(|mode_info| mode_info.kmap_paths())
+            .collect()
+    }
+
     /// Return the anagram num corresponding to each anagram mod, in the same
     /// order as the `anagram_mods` vector.
     pub fn get_anagram_mod_numbers(&self) -> Result<Vec<AnagramNum>, Error> {
@@ -108,7 +148,7 @@ impl AllData {
         chords
     }
 
-    fn get_all_names(&self) -> Vec<Name> {
+    pub fn get_all_names(&self) -> Vec<Name> {
         self.sequences
             .names()
             .chain(self.anagram_mods.iter())
@@ -149,32 +189,37 @@ impl AllData {
         // TODO think about borrowck
         // TODO this is pretty slow
         let mut chords = BTreeMap::new();
+        let mut spans = BTreeMap::new();
         let names = self.get_all_names();
         for mode in self.modes.keys() {
             let mut mode_chords = BTreeMap::new();
+            let mut mode_spans = BTreeMap::new();
             for name in &names {
                 if let Some(chord) = self.get_chord_in_mode(name, mode) {
                     // TODO speed up by fetching all anagram chords in advance?
+                    // If we couldn't incorporate the anagram number into the
+                    // chord, skip it.
                     if let Some(new) = self.incorporate_anagram(chord, mode) {
                         mode_chords.insert(name.to_owned(), new);
+                        if let Some(span) = self.get_span_in_mode(name, mode) {
+                            mode_spans.insert(name.to_owned(), span.to_owned());
+                        }
                     }
-                    // Otherwise we couldn't incorporate the anagram number into
-                    // the chord, so skip it.
                 }
             }
             chords.insert(mode.to_owned(), mode_chords);
+            spans.insert(mode.to_owned(), mode_spans);
         }
         // TODO use references instead of cloning spellings
         Ok(TutorData {
             chords,
+            spans,
             spellings: self.spellings.clone(),
             chord_spec: self.chord_spec.clone(),
+            dictionary: self.dictionary.clone(),
+            word_space_position: self.user_options.word_space_position,
         })
     }
-
-    pub fn board(&self) -> BoardName {
-        self.board
-    }
 }
 
 impl AllChordMaps {
@@ -223,17 +268,21 @@ impl AllChordMaps {
         self.maps.entry(kmap).or_default().insert(name, chord)
     }
 
-    // TODO is it worth having this separate method?
-    // It's not actually more efficient...
     pub fn insert_map(
         &mut self,
-        named_chords: BTreeMap<Name, Chord<KmapOrder>>,
+        chord_map: ChordMap,
         kmap: KmapPath,
     ) -> Result<(), Error> {
-        for (name, chord) in named_chords {
-            self.insert(name, chord, kmap.clone())?;
-        }
-        Ok(())
+        self.maps.entry(kmap).or_default().append(chord_map)
+    }
+
+    /// Get where the chord was defined in the kmap file, if it was.
+    pub fn span(
+        &self,
+        chord_name: &Name,
+        kmap: &KmapPath,
+    ) -> Option<&SourceSpan> {
+        self.maps.get(kmap)?.span(chord_name)
     }
 
     pub fn iter(&self) -> impl Iterator<Item = (&KmapPath, &ChordMap)> {
@@ -318,57 +367,82 @@ impl AllSeqMaps {
             .max()
             .unwrap_or(0)
     }
-
-    pub fn all_keypresses(&self) -> Vec<KeyPress> {
-        let mut v = Vec::new();
-        for seq_map in self.maps.values() {
-            for seq in seq_map.0.values() {
-                v.extend(seq.keypresses().cloned())
-            }
-        }
-        v
-    }
 }
 
 impl ChordMap {
     pub fn get(&self, name: &Name) -> Option<&Chord<KmapOrder>> {
-        self.0.get(name)
+        self.chords.get(name)
     }
 
     pub fn get_result(&self, name: &Name) -> Result<&Chord<KmapOrder>, Error> {
         // TODO merge with get?
-        self.0.get(name).ok_or_else(|| Error::LookupErr {
+        self.chords.get(name).ok_or_else(|| Error::LookupErr {
             key: name.into(),
             container: "chord map".to_owned(),
         })
     }
 
-    fn insert(
+    pub fn span(&self, name: &Name) -> Option<&SourceSpan> {
+        self.spans.get(name)
+    }
+
+    /// Insert a chord that wasn't read from a kmap file.
+    pub fn insert(
         &mut self,
         name: Name,
         chord: Chord<KmapOrder>,
     ) -> Result<(), Error> {
-        if self.0.contains_key(&name) {
-            return Err(Error::ConflictErr {
+        if self.chords.contains_key(&name) {
+            let err = Error::ConflictErr {
                 key: name.clone().into(),
                 container: "chord map".into(),
+            };
+            return Err(match self.span(&name) {
+                Some(span) => err.with_context(|| {
+                    format!("'{}' is already defined at {}", name, span)
+                }),
+                None => err,
             });
         }
 
-        self.0.insert(name, chord);
+        self.chords.insert(name, chord);
+        Ok(())
+    }
+
+    /// Insert a chord that was defined at the given place in a kmap file.
+    pub fn insert_with_span(
+        &mut self,
+        name: Name,
+        chord: Chord<KmapOrder>,
+        span: SourceSpan,
+    ) -> Result<(), Error> {
+        self.insert(name.clone(), chord)
+            .with_context(|| format!("Failed to add chord at {}", span))?;
+        self.spans.insert(name, span);
+        Ok(())
+    }
+
+    fn append(&mut self, other: ChordMap) -> Result<(), Error> {
+        let ChordMap { chords, mut spans } = other;
+        for (name, chord) in chords {
+            match spans.remove(&name) {
+                Some(span) => self.insert_with_span(name, chord, span)?,
+                None => self.insert(name, chord)?,
+            }
+        }
         Ok(())
     }
 
     pub fn iter(&self) -> impl Iterator<Item = (&Name, &Chord<KmapOrder>)> {
-        self.0.iter()
+        self.chords.iter()
     }
 
     pub fn names(&self) -> impl Iterator<Item = &Name> {
-        self.0.keys()
+        self.chords.keys()
     }
 
     fn max_anagram_num(&self) -> AnagramNum {
-        self.0
+        self.chords
             .values()
             .map(|chord| chord.anagram_num)
             .max()
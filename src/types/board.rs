@@ -0,0 +1,184 @@
+use error::Error;
+use types::{CCode, Pin, ToC, Validate};
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoardName {
+    #[serde(rename = "TEENSY_LC")]
+    TeensyLC,
+    #[serde(rename = "TEENSY_32")]
+    Teensy32,
+    #[serde(rename = "TEENSY_35")]
+    Teensy35,
+    #[serde(rename = "TEENSY_36")]
+    Teensy36,
+    #[serde(rename = "TEENSY_40")]
+    Teensy40,
+    #[serde(rename = "TEENSY_41")]
+    Teensy41,
+    #[serde(rename = "PRO_MICRO")]
+    ProMicro,
+    #[serde(rename = "RASPBERRY_PI_PICO")]
+    RaspberryPiPico,
+}
+
+/// Everything we need to know about a board to validate the settings and
+/// build the firmware for it.
+#[derive(Debug, Clone, Copy)]
+pub struct BoardInfo {
+    /// The name used in the settings file, which is also defined as a macro in
+    /// the generated config so the firmware can check it's being compiled for
+    /// the right board.
+    pub c_name: &'static str,
+    /// The fully qualified board name (FQBN) used by the Arduino IDE,
+    /// including any board options the firmware needs.
+    pub ide_name: &'static str,
+    /// The inclusive ranges of pin numbers that are broken out on the board.
+    pub pins: &'static [(u8, u8)],
+    /// The size of a pointer in bytes.
+    pub pointer_bytes: usize,
+    /// The largest alignment of any type, in bytes. Smaller types are aligned
+    /// to their own size.
+    pub max_alignment: usize,
+    /// Whether the firmware can go into standby while waiting for a switch to
+    /// be pressed. This uses timer registers specific to the Teensy LC.
+    pub standby_interrupts: bool,
+}
+
+const ALL_BOARDS: [BoardName; 8] = [
+    BoardName::TeensyLC,
+    BoardName::Teensy32,
+    BoardName::Teensy35,
+    BoardName::Teensy36,
+    BoardName::Teensy40,
+    BoardName::Teensy41,
+    BoardName::ProMicro,
+    BoardName::RaspberryPiPico,
+];
+
+////////////////////////////////////////////////////////////////////////////////
+
+impl BoardName {
+    pub fn info(self) -> BoardInfo {
+        match self {
+            BoardName::TeensyLC => BoardInfo {
+                c_name: "TEENSY_LC",
+                ide_name: "teensy:avr:teensyLC:usb=serialhid",
+                pins: &[(0, 26)],
+                pointer_bytes: 4,
+                max_alignment: 4,
+                standby_interrupts: true,
+            },
+            BoardName::Teensy32 => BoardInfo {
+                c_name: "TEENSY_32",
+                ide_name: "teensy:avr:teensy31:usb=serialhid",
+                pins: &[(0, 33)],
+                pointer_bytes: 4,
+                max_alignment: 4,
+                standby_interrupts: false,
+            },
+            BoardName::Teensy35 => BoardInfo {
+                c_name: "TEENSY_35",
+                ide_name: "teensy:avr:teensy35:usb=serialhid",
+                pins: &[(0, 57)],
+                pointer_bytes: 4,
+                max_alignment: 4,
+                standby_interrupts: false,
+            },
+            BoardName::Teensy36 => BoardInfo {
+                c_name: "TEENSY_36",
+                ide_name: "teensy:avr:teensy36:usb=serialhid",
+                pins: &[(0, 57)],
+                pointer_bytes: 4,
+                max_alignment: 4,
+                standby_interrupts: false,
+            },
+            BoardName::Teensy40 => BoardInfo {
+                c_name: "TEENSY_40",
+                ide_name: "teensy:avr:teensy40:usb=serialhid",
+                pins: &[(0, 39)],
+                pointer_bytes: 4,
+                max_alignment: 4,
+                standby_interrupts: false,
+            },
+            BoardName::Teensy41 => BoardInfo {
+                c_name: "TEENSY_41",
+                ide_name: "teensy:avr:teensy41:usb=serialhid",
+                pins: &[(0, 54)],
+                pointer_bytes: 4,
+                max_alignment: 4,
+                standby_interrupts: false,
+            },
+            BoardName::ProMicro => BoardInfo {
+                c_name: "PRO_MICRO",
+                ide_name: "sparkfun:avr:promicro:cpu=16MHzatmega32U4",
+                // Pins 11-13 and 17 aren't broken out.
+                pins: &[(0, 10), (14, 16), (18, 21)],
+                // The ATmega32u4 is an 8-bit AVR, which doesn't align anything.
+                pointer_bytes: 2,
+                max_alignment: 1,
+                standby_interrupts: false,
+            },
+            BoardName::RaspberryPiPico => BoardInfo {
+                c_name: "RASPBERRY_PI_PICO",
+                ide_name: "rp2040:rp2040:rpipico",
+                // Pins 23-25 are used internally.
+                pins: &[(0, 22), (26, 28)],
+                pointer_bytes: 4,
+                max_alignment: 4,
+                standby_interrupts: false,
+            },
+        }
+    }
+
+    /// The highest pin number on any supported board.
+    pub fn max_pin_num() -> u8 {
+        ALL_BOARDS
+            .iter()
+            .flat_map(|board| board.info().pins.iter())
+            .map(|&(_, max)| max)
+            .max()
+            .expect("no boards are supported")
+    }
+
+    pub fn validate_pin(self, pin: Pin) -> Result<(), Error> {
+        let info = self.info();
+        let num = u8::from(pin);
+        if info.pins.iter().any(|&(min, max)| num >= min && num <= max) {
+            Ok(())
+        } else {
+            Err(Error::BadValueErr {
+                thing: format!("pin number for {}", info.c_name),
+                value: num.to_string(),
+            })
+        }
+    }
+
+    /// Return an error if the board doesn't support a feature that the
+    /// settings enabled.
+    pub fn ensure_supports(
+        self,
+        feature: &str,
+        supported: bool,
+    ) -> Result<(), Error> {
+        if supported {
+            Ok(())
+        } else {
+            Err(Error::Unsupported {
+                feature: feature.to_owned(),
+                board: self.info().c_name.to_owned(),
+            })
+        }
+    }
+}
+
+impl ToC for BoardName {
+    fn to_c(self) -> CCode {
+        self.info().c_name.to_c()
+    }
+}
+
+impl Validate for BoardName {
+    fn validate(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
@@ -1,22 +1,27 @@
-use std::borrow::Cow;
+use std::cmp::Reverse;
 use std::collections::{BTreeMap, HashMap, HashSet};
-use std::fmt::{self, Display};
+use std::fmt;
 
 use types::{
-    AllData, AnagramNum, Chord, KmapOrder, KmapPath, ModeInfo, ModeName, Name,
+    AllData, AnagramNum, Chord, Diagnostic, DiagnosticKind, KmapOrder,
+    KmapPath, ModeInfo, ModeName, Name, Severity, SourceSpan, WordFrequencies,
+    Wordlike,
 };
 
-/// The Checker warns about sub-optimal configuration, like conflicting chords
+/// The Checker reports sub-optimal configuration, like conflicting chords
 /// or skipped anagram numbers. Any config issues that would break the firmware
 /// should be caught in the loading or formatting code instead.
 #[derive(Debug)]
-struct Checker {
+struct Checker<'a> {
     // TODO use more references, instead of duplicating lots of stuff
     reverse_kmaps: HashMap<KmapPath, HashMap<Chord<KmapOrder>, AnagramSet>>,
     seq_names: HashSet<Name>,
     chord_names: HashSet<Name>,
     word_mod_names: HashSet<Name>,
     modes: BTreeMap<ModeName, ModeInfo>,
+    /// The text of each dictionary word, by name.
+    words: HashMap<Name, &'a str>,
+    frequencies: &'a WordFrequencies,
 }
 
 #[derive(Debug)]
@@ -25,20 +30,60 @@ struct AnagramSet(HashMap<AnagramNum, Vec<Name>>);
 ////////////////////////////////////////////////////////////////////////////////
 
 impl AllData {
-    pub fn check(&self) {
-        let checker = self.checker();
-        checker.check_unused();
-        checker.check_conflicts();
-        checker.check_gaming_modes();
+    /// Print any problems found by the checker. The word frequencies are only
+    /// used to check the order of anagrams, and can be empty.
+    pub fn check(&self, frequencies: &WordFrequencies) {
+        for diagnostic in self.diagnostics(frequencies) {
+            println!("{}", diagnostic);
+        }
+    }
+
+    /// Return all the problems found by the checker, sorted by kmap.
+    pub fn diagnostics(
+        &self,
+        frequencies: &WordFrequencies,
+    ) -> Vec<Diagnostic> {
+        let checker = self.checker(frequencies);
+        let mut diagnostics = Vec::new();
+        checker.check_unused(&mut diagnostics);
+        checker.check_conflicts(&mut diagnostics);
+        checker.check_anagram_frequencies(&mut diagnostics);
+        checker.check_gaming_modes(&mut diagnostics);
+        let mut diagnostics: Vec<_> = diagnostics
+            .into_iter()
+            .map(|diagnostic| {
+                let span = self.first_span(&diagnostic);
+                diagnostic.at(span)
+            })
+            .collect();
+        diagnostics.sort();
+        diagnostics
+    }
+
+    /// Find the earliest place in the diagnostic's kmap where one of its
+    /// chords was defined.
+    fn first_span(&self, diagnostic: &Diagnostic) -> Option<&SourceSpan> {
+        let kmap = diagnostic.kmap.as_ref()?;
+        diagnostic
+            .names
+            .iter()
+            .filter_map(|name| self.chords.span(name, kmap))
+            .min()
     }
 
-    fn checker(&self) -> Checker {
+    fn checker<'a>(&'a self, frequencies: &'a WordFrequencies) -> Checker<'a> {
         Checker {
             reverse_kmaps: self.reverse_chords(),
             seq_names: self.sequences.names().cloned().collect(),
             chord_names: self.chords.names().cloned().collect(),
             word_mod_names: self.word_mods(),
             modes: self.modes.clone(),
+            words: self
+                .dictionary
+                .iter()
+                .map(|word| (word.name(), word.word.as_str()))
+                .collect(),
+            frequencies,
         }
     }
 
@@ -71,69 +116,145 @@ impl AllData {
     }
 }
 
-impl Checker {
+impl<'a> Checker<'a> {
     /// If two names have the same chord, or there's a skipped anagram
-    /// number, print out that whole set of names.
-    fn check_conflicts(&self) {
+    /// number, report that whole set of names.
+    fn check_conflicts(&self, out: &mut Vec<Diagnostic>) {
         // TODO option to check for mode conflicts instead
         for (kmap, reversed) in &self.reverse_kmaps {
-            let heading = format!(
-                "\nConflicting chords (in parens) or skipped anagrams \
-                 (\"{}\") in \'{}\':",
-                AnagramSet::missing_symbol(),
-                kmap
-            );
-            print_iter(
-                heading,
-                reversed
-                    .iter()
-                    .filter(|&(_chord, set)| {
-                        set.is_invalid(&self.word_mod_names)
-                    })
-                    .map(|(_chord, set)| set),
-            );
+            for set in reversed.values() {
+                if set.has_chord_conflict(&self.word_mod_names) {
+                    out.push(
+                        Diagnostic::new(
+                            Severity::Error,
+                            DiagnosticKind::ChordConflict,
+                            format!("Conflicting chords (in parens): {}", set),
+                        )
+                        .in_kmap(kmap)
+                        .with_names(set.all_names()),
+                    );
+                }
+                if set.has_skipped_anagram() {
+                    out.push(
+                        Diagnostic::new(
+                            Severity::Warning,
+                            DiagnosticKind::SkippedAnagram,
+                            format!(
+                                "Skipped anagrams (\"{}\"): {}",
+                                AnagramSet::missing_symbol(),
+                                set
+                            ),
+                        )
+                        .in_kmap(kmap)
+                        .with_names(set.all_names()),
+                    );
+                }
+            }
         }
     }
 
-    /// Compare the stored chords and sequences (and word mods), and print
+    /// If a word with anagram 0 is less common than a word that shares its
+    /// chord, report both words. The more common word should be the one that
+    /// doesn't need any anagram mods.
+    fn check_anagram_frequencies(&self, out: &mut Vec<Diagnostic>) {
+        for (kmap, reversed) in &self.reverse_kmaps {
+            for set in reversed.values() {
+                let (rarer, (more_common, anagram_num)) =
+                    match set.rarer_word_first(&self.words, self.frequencies) {
+                        Some(pair) => pair,
+                        None => continue,
+                    };
+                out.push(
+                    Diagnostic::new(
+                        Severity::Warning,
+                        DiagnosticKind::RarerWordFirst,
+                        format!(
+                            "Rarer word '{}' has anagram 0, but the more \
+                             common word '{}' has anagram {}",
+                            self.words[rarer],
+                            self.words[more_common],
+                            anagram_num
+                        ),
+                    )
+                    .in_kmap(kmap)
+                    .with_names(vec![rarer, more_common]),
+                );
+            }
+        }
+    }
+
+    /// Compare the stored chords and sequences (and word mods), and report
     /// any names that don't appear in both.
-    fn check_unused(&self) {
+    fn check_unused(&self, out: &mut Vec<Diagnostic>) {
         let seqs_and_mods: HashSet<_> = self
             .seq_names
             .union(&self.word_mod_names)
             .cloned()
             .collect();
-        print_iter(
-            "Unused chords:",
-            self.chord_names.difference(&seqs_and_mods),
-        );
-        print_iter(
-            "Unused sequences:",
-            seqs_and_mods.difference(&self.chord_names),
-        );
+        for (kmap, reversed) in &self.reverse_kmaps {
+            let unused = reversed
+                .values()
+                .flat_map(|set| set.all_names())
+                .filter(|name| !seqs_and_mods.contains(name));
+            for name in unused {
+                out.push(
+                    Diagnostic::new(
+                        Severity::Warning,
+                        DiagnosticKind::UnusedChord,
+                        format!("Unused chord: '{}'", name),
+                    )
+                    .in_kmap(kmap)
+                    .with_names(Some(name)),
+                );
+            }
+        }
+        for name in seqs_and_mods.difference(&self.chord_names) {
+            out.push(
+                Diagnostic::new(
+                    Severity::Warning,
+                    DiagnosticKind::UnusedSequence,
+                    format!("Unused sequence: '{}'", name),
+                )
+                .with_names(Some(name)),
+            );
+        }
     }
 
-    fn check_gaming_modes(&self) {
+    fn check_gaming_modes(&self, out: &mut Vec<Diagnostic>) {
         for (mode_name, mode_info) in &self.modes {
             if !mode_info.gaming {
                 continue;
             }
             for kmap_info in &mode_info.keymaps {
                 if kmap_info.use_words {
-                    println!(
-                        "Don't use words in a gaming mode, \
-                         multi-switch chords won't work: '{}' \n",
-                        mode_name
+                    out.push(
+                        Diagnostic::new(
+                            Severity::Error,
+                            DiagnosticKind::WordsInGamingMode,
+                            format!(
+                                "Don't use words in a gaming mode, \
+                                 multi-switch chords won't work: '{}'",
+                                mode_name
+                            ),
+                        )
+                        .in_kmap(&kmap_info.file),
+                    );
+                }
+                for name in self.multiswitch_chords(&kmap_info.file) {
+                    out.push(
+                        Diagnostic::new(
+                            Severity::Warning,
+                            DiagnosticKind::ChordInGamingMode,
+                            format!(
+                                "Multi-switch chord won't work in gaming \
+                                 mode '{}': '{}'",
+                                mode_name, name
+                            ),
+                        )
+                        .in_kmap(&kmap_info.file)
+                        .with_names(Some(name)),
                     );
                 }
-                print_iter(
-                    format!(
-                        "Multi-switch chords won't work in gaming modes.\nIn {}'s {}:",
-                        mode_name,
-                        kmap_info.file
-                    ),
-                    self.multiswitch_chords(&kmap_info.file),
-                );
             }
         }
     }
@@ -163,16 +284,10 @@ impl AnagramSet {
             .push(name);
     }
 
-    fn is_invalid(&self, word_mod_names: &HashSet<Name>) -> bool {
-        if self.has_chord_conflict(word_mod_names) {
-            return true;
-        }
-        for num in self.max_anagram().up_to() {
-            if !self.contains_anagram(num) {
-                return true;
-            }
-        }
-        false
+    fn has_skipped_anagram(&self) -> bool {
+        self.max_anagram()
+            .up_to()
+            .any(|num| !self.contains_anagram(num))
     }
 
     fn has_chord_conflict(&self, word_mod_names: &HashSet<Name>) -> bool {
@@ -190,6 +305,39 @@ impl AnagramSet {
         false
     }
 
+    /// Return the first word with anagram 0 if a more common word in the set
+    /// has a higher anagram number, along with the most common such word.
+    fn rarer_word_first(
+        &self,
+        words: &HashMap<Name, &str>,
+        frequencies: &WordFrequencies,
+    ) -> Option<(&Name, (&Name, AnagramNum))> {
+        let frequency =
+            |name: &Name| words.get(name).map(|w| frequencies.get(w));
+        let first = self
+            .0
+            .get(&AnagramNum::default())?
+            .iter()
+            .find(|name| words.contains_key(*name))?;
+        let (more_common, anagram_num, count) = self
+            .0
+            .iter()
+            .filter(|(anagram_num, _)| !anagram_num.is_default())
+            .flat_map(|(&anagram_num, names)| {
+                names.iter().map(move |name| (name, anagram_num))
+            })
+            .filter_map(|(name, anagram_num)| {
+                frequency(name).map(|count| (name, anagram_num, count))
+            })
+            // Break ties by name, so the result doesn't depend on hash order.
+            .max_by_key(|&(name, _, count)| (count, Reverse(name)))?;
+        if count > frequency(first)? {
+            Some((first, (more_common, anagram_num)))
+        } else {
+            None
+        }
+    }
+
     fn contains_anagram(&self, anagram_num: AnagramNum) -> bool {
         self.0.contains_key(&anagram_num)
     }
@@ -239,25 +387,6 @@ impl fmt::Display for AnagramSet {
     }
 }
 
-/// If the iterator is non-empty, print the given heading and then print each
-/// element of the iterator on a separate line with some spaces before it.
-fn print_iter<'a, T, U>(heading: U, iter: T)
-where
-    T: Iterator,
-    <T as Iterator>::Item: Display,
-    U: Into<Cow<'a, str>>,
-{
-    let mut iter = iter.peekable();
-    if iter.peek().is_none() {
-        return;
-    }
-    println!("{}", heading.into());
-    for thing in iter {
-        println!("  {}", thing);
-    }
-    println!();
-}
-
 /// Return true if one is a `word_mod` or `anagram_mod`, and the other cannot be
 /// used in a word chord (letters, mostly). This lets us map
 /// `word_mods`/`anagram_mods` to chords that do something different when
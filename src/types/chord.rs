@@ -111,6 +111,37 @@ where
         Ok(new)
     }
 
+    /// Return true if every switch pressed in the other chord is also pressed
+    /// in this one. Anagram numbers are ignored.
+    pub fn contains(&self, other: &Self) -> bool {
+        assert_eq!(self.len(), other.len());
+        let mut overlap = self.switches.clone();
+        overlap.intersect(&other.switches);
+        overlap == other.switches
+    }
+
+    /// Return a new chord containing only the switches that are pressed in
+    /// both this chord and the given one. The anagram number of this chord
+    /// is kept.
+    pub fn intersection(&self, other: &Self) -> Self {
+        assert_eq!(self.len(), other.len());
+        let mut new = self.to_owned();
+        new.switches.intersect(&other.switches);
+        new
+    }
+
+    /// Unpress all the switches in this chord that are pressed in the other
+    /// chord.
+    pub fn subtract_mut(&mut self, other: &Self) {
+        assert_eq!(self.len(), other.len());
+        self.switches.difference(&other.switches);
+    }
+
+    /// Return true if no switches are pressed.
+    pub fn is_empty(&self) -> bool {
+        self.switches.none()
+    }
+
     fn len(&self) -> usize {
         self.switches.len()
     }
@@ -146,6 +177,21 @@ impl Chord<KmapOrder> {
     pub fn iter(&self) -> bit_vec::Iter<u8> {
         self.switches.iter()
     }
+
+    /// Rearrange the switches, like when mirroring a kmap. The permutation
+    /// must not change the number of switches.
+    pub fn permute(&self, permutation: &Permutation) -> Result<Self, Error> {
+        Ok(Self {
+            switches: permutation.permute(&self.switches)?,
+            anagram_num: self.anagram_num,
+            order: PhantomData,
+        })
+    }
+
+    /// Press or unpress the switch at the given index (in kmap order).
+    pub fn set_switch(&mut self, index: usize, is_pressed: bool) {
+        self.switches.set(index, is_pressed);
+    }
 }
 
 // impl fmt::Debug for Chord<T>
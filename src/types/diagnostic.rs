@@ -0,0 +1,162 @@
+use serde_json;
+use std::fmt;
+
+use error::Error;
+use types::{KmapPath, Name, SourceSpan};
+
+/// A single problem found by the checker, in a form that can be printed for
+/// people or serialized for other tools.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Diagnostic {
+    pub kmap: Option<KmapPath>,
+    pub line: Option<usize>,
+    pub block: Option<usize>,
+    pub severity: Severity,
+    pub kind: DiagnosticKind,
+    pub names: Vec<Name>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticKind {
+    UnusedChord,
+    UnusedSequence,
+    ChordConflict,
+    SkippedAnagram,
+    RarerWordFirst,
+    WordsInGamingMode,
+    ChordInGamingMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticFormat {
+    Text,
+    Json,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+impl Diagnostic {
+    pub fn new(
+        severity: Severity,
+        kind: DiagnosticKind,
+        message: String,
+    ) -> Self {
+        Self {
+            kmap: None,
+            line: None,
+            block: None,
+            severity,
+            kind,
+            names: Vec::new(),
+            message,
+        }
+    }
+
+    pub fn in_kmap(mut self, kmap: &KmapPath) -> Self {
+        self.kmap = Some(kmap.to_owned());
+        self
+    }
+
+    /// Point to the block in the kmap file where one of the diagnostic's chords
+    /// was defined.
+    pub fn at(mut self, span: Option<&SourceSpan>) -> Self {
+        if let Some(span) = span {
+            self.kmap = Some(span.kmap.to_owned());
+            self.line = Some(span.line);
+            self.block = Some(span.block);
+        }
+        self
+    }
+
+    pub fn with_names<'a, T>(mut self, names: T) -> Self
+    where
+        T: IntoIterator<Item = &'a Name>,
+    {
+        self.names = names.into_iter().cloned().collect();
+        self.names.sort();
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Format all the diagnostics, either one per line in a compiler-like
+    /// style, or as a json array.
+    pub fn render_all(
+        diagnostics: &[Diagnostic],
+        format: DiagnosticFormat,
+    ) -> Result<String, Error> {
+        Ok(match format {
+            DiagnosticFormat::Text => diagnostics
+                .iter()
+                .map(|diagnostic| format!("{}\n", diagnostic))
+                .collect(),
+            DiagnosticFormat::Json => {
+                serde_json::to_string_pretty(diagnostics)?
+            }
+        })
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.kmap, self.line) {
+            (Some(kmap), Some(line)) => write!(f, "{}:{}: ", kmap, line)?,
+            (Some(kmap), None) => write!(f, "{}: ", kmap)?,
+            _ => (),
+        }
+        write!(f, "{}[{}]: {}", self.severity, self.kind, self.message)?;
+        if let Some(block) = self.block {
+            write!(f, " (block {})", block)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            DiagnosticKind::UnusedChord => "unused_chord",
+            DiagnosticKind::UnusedSequence => "unused_sequence",
+            DiagnosticKind::ChordConflict => "chord_conflict",
+            DiagnosticKind::SkippedAnagram => "skipped_anagram",
+            DiagnosticKind::RarerWordFirst => "rarer_word_first",
+            DiagnosticKind::WordsInGamingMode => "words_in_gaming_mode",
+            DiagnosticKind::ChordInGamingMode => "chord_in_gaming_mode",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl DiagnosticFormat {
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        match name {
+            "text" => Ok(DiagnosticFormat::Text),
+            "json" => Ok(DiagnosticFormat::Json),
+            _ => Err(Error::BadValueErr {
+                thing: "diagnostic format".into(),
+                value: name.into(),
+            }),
+        }
+    }
+}
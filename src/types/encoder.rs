@@ -0,0 +1,243 @@
+use bit_vec::BitVec;
+use std::collections::BTreeMap;
+
+use error::Error;
+use types::huffman::{count_keypress, increment, SymbolCounts, SymbolKind};
+use types::{
+    AllSeqMaps, CCode, HuffmanTable, KeyPress, SeqType, Sequence, ToC,
+    Validate, Word, WordFrequencies, Wordlike,
+};
+
+/// Fragments are only worth storing if they save space in several sequences.
+const MIN_FRAGMENT_USES: u64 = 4;
+
+/// Keep the fragment table small, since the firmware searches it linearly.
+const MAX_FRAGMENTS: usize = 64;
+
+const MAX_FRAGMENT_LENGTH: usize = 16;
+
+always_valid_enum! {
+    #[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+    pub enum SequenceEncoding {
+        /// Huffman code each key and modifier separately.
+        #[default]
+        Huffman,
+        /// Also give huffman codes to runs of keypresses that are shared
+        /// between many sequences, like common prefixes and suffixes of words.
+        Fragments,
+    }
+}
+
+impl SequenceEncoding {
+    pub const ALL: [SequenceEncoding; 2] =
+        [SequenceEncoding::Huffman, SequenceEncoding::Fragments];
+}
+
+/// Encodes sequences as bits for the firmware lookup tables.
+#[derive(Debug, Clone)]
+pub struct SequenceEncoder {
+    pub encoding: SequenceEncoding,
+    pub huffman_table: HuffmanTable,
+    /// Runs of keypresses that get their own huffman codes. Only the
+    /// `Fragments` encoding uses them.
+    pub fragments: Vec<Sequence>,
+}
+
+/// A piece of an encoded sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeqToken<'a> {
+    KeyPress(&'a KeyPress),
+    /// The index of a fragment.
+    Fragment(usize),
+}
+
+impl SequenceEncoder {
+    /// Choose codes for all the sequences. If word frequencies are given,
+    /// each word's sequence is also counted once for every time the word
+    /// appears in them, so that commonly typed words get shorter codes.
+    pub fn new(
+        encoding: SequenceEncoding,
+        sequences: &AllSeqMaps,
+        dictionary: &[Word],
+        frequencies: Option<&WordFrequencies>,
+    ) -> Result<Self, Error> {
+        let weighted = weighted_sequences(sequences, dictionary, frequencies)?;
+        let fragments = match encoding {
+            SequenceEncoding::Huffman => Vec::new(),
+            // Fragments are stored once per sequence, so choose them by how
+            // many sequences they'd shorten, not by how often the words are
+            // typed.
+            SequenceEncoding::Fragments => choose_fragments(
+                &weighted.iter().map(|&(seq, _)| seq).collect::<Vec<_>>(),
+            ),
+        };
+        let mut encoder = SequenceEncoder {
+            encoding,
+            huffman_table: HuffmanTable(BTreeMap::new()),
+            fragments,
+        };
+
+        let mut counts = SymbolCounts::new();
+        for (seq, weight) in &weighted {
+            for token in encoder.tokens(seq) {
+                match token {
+                    SeqToken::KeyPress(keypress) => {
+                        count_keypress(&mut counts, keypress, *weight)
+                    }
+                    SeqToken::Fragment(index) => increment(
+                        &mut counts,
+                        Self::fragment_code(index),
+                        SymbolKind::Fragment,
+                        *weight,
+                    ),
+                }
+            }
+        }
+        // Fragments are stored one keypress at a time.
+        for fragment in &encoder.fragments {
+            for keypress in fragment.keypresses() {
+                count_keypress(&mut counts, keypress, 1);
+            }
+        }
+        encoder.huffman_table = HuffmanTable::from_counts(counts)?;
+        Ok(encoder)
+    }
+
+    /// Split the sequence into keypresses and fragments, always using the
+    /// longest fragment that matches.
+    pub fn tokens<'a>(&self, seq: &'a Sequence) -> Vec<SeqToken<'a>> {
+        let mut tokens = Vec::new();
+        let mut rest = &seq.0[..];
+        while !rest.is_empty() {
+            let fragment = self
+                .fragments
+                .iter()
+                .enumerate()
+                .filter(|(_, fragment)| rest.starts_with(&fragment.0))
+                .max_by_key(|(_, fragment)| fragment.len());
+            match fragment {
+                Some((index, fragment)) => {
+                    tokens.push(SeqToken::Fragment(index));
+                    rest = &rest[fragment.len()..];
+                }
+                None => {
+                    tokens.push(SeqToken::KeyPress(&rest[0]));
+                    rest = &rest[1..];
+                }
+            }
+        }
+        tokens
+    }
+
+    /// Decode an encoded sequence, the same way the firmware does.
+    pub fn decode(&self, bits: &BitVec<u8>) -> Result<Sequence, Error> {
+        self.huffman_table.decode(bits, |code| {
+            let index = Self::fragment_index(code)?;
+            self.fragments
+                .get(index)
+                .cloned()
+                .ok_or_else(|| Error::LookupErr {
+                    key: index.to_string(),
+                    container: "fragment table".into(),
+                })
+        })
+    }
+
+    /// The key of the fragment in the huffman table. It's also used as the
+    /// fragment's `key_code` in the firmware, so it must be a valid C
+    /// expression.
+    pub fn fragment_code(index: usize) -> CCode {
+        format!("{} /* fragment */", index).to_c()
+    }
+
+    /// The reverse of `fragment_code()`.
+    pub fn fragment_index(code: &CCode) -> Result<usize, Error> {
+        code.0
+            .split_whitespace()
+            .next()
+            .and_then(|index| index.parse().ok())
+            .ok_or_else(|| Error::BadValueErr {
+                thing: "fragment code".into(),
+                value: code.to_string(),
+            })
+    }
+}
+
+fn weighted_sequences<'a>(
+    sequences: &'a AllSeqMaps,
+    dictionary: &[Word],
+    frequencies: Option<&WordFrequencies>,
+) -> Result<Vec<(&'a Sequence, u64)>, Error> {
+    let mut word_counts = BTreeMap::new();
+    if let Some(frequencies) = frequencies {
+        for word in dictionary {
+            word_counts.insert(word.name(), frequencies.get(&word.word));
+        }
+    }
+
+    let mut weighted = Vec::new();
+    for &seq_type in sequences.seq_types() {
+        for name in sequences.get_seq_map(seq_type)?.names() {
+            let count = match seq_type {
+                SeqType::Word => word_counts.get(name).cloned().unwrap_or(0),
+                _ => 0,
+            };
+            weighted.push((sequences.get(name, seq_type)?, 1 + count));
+        }
+    }
+    Ok(weighted)
+}
+
+/// Choose fragments by repeatedly merging the most common pair of adjacent
+/// runs of keypresses, starting from single keypresses.
+fn choose_fragments(sequences: &[&Sequence]) -> Vec<Sequence> {
+    // The runs of each sequence, as ranges of keypress indices.
+    let mut all_runs: Vec<Vec<(usize, usize)>> = sequences
+        .iter()
+        .map(|seq| (0..seq.len()).map(|i| (i, i + 1)).collect())
+        .collect();
+
+    let mut fragments = Vec::new();
+    while fragments.len() < MAX_FRAGMENTS {
+        let mut pair_counts: BTreeMap<&[KeyPress], u64> = BTreeMap::new();
+        for (seq, runs) in sequences.iter().zip(&all_runs) {
+            for pair in runs.windows(2) {
+                let run = &seq.0[pair[0].0..pair[1].1];
+                if run.len() <= MAX_FRAGMENT_LENGTH {
+                    *pair_counts.entry(run).or_insert(0) += 1;
+                }
+            }
+        }
+        let fragment = match pair_counts
+            .into_iter()
+            .max_by_key(|&(_, count)| count)
+        {
+            Some((run, count)) if count >= MIN_FRAGMENT_USES => run.to_vec(),
+            _ => break,
+        };
+        for (seq, runs) in sequences.iter().zip(&mut all_runs) {
+            merge_runs(seq, runs, &fragment);
+        }
+        fragments.push(Sequence(fragment));
+    }
+    fragments
+}
+
+fn merge_runs(
+    seq: &Sequence,
+    runs: &mut Vec<(usize, usize)>,
+    fragment: &[KeyPress],
+) {
+    let mut merged = Vec::with_capacity(runs.len());
+    let mut i = 0;
+    while i < runs.len() {
+        if i + 1 < runs.len() && seq.0[runs[i].0..runs[i + 1].1] == *fragment {
+            merged.push((runs[i].0, runs[i + 1].1));
+            i += 2;
+        } else {
+            merged.push(runs[i]);
+            i += 1;
+        }
+    }
+    *runs = merged;
+}
@@ -4,7 +4,7 @@ use std::collections::binary_heap::BinaryHeap;
 use std::collections::BTreeMap;
 
 use error::{Error, ResultExt};
-use types::{CCode, KeyPress};
+use types::{CCode, KeyPress, Sequence};
 use util::ensure_u8;
 
 #[derive(Debug, Clone)]
@@ -12,16 +12,28 @@ pub struct HuffmanTable(pub BTreeMap<CCode, HuffmanEntry>);
 
 #[derive(Debug, Clone)]
 pub struct HuffmanEntry {
-    pub is_mod: bool,
+    pub kind: SymbolKind,
     bits: BitVec<u8>,
 }
 
+/// What a huffman code stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Key,
+    Mod,
+    /// A run of keypresses from the encoder's fragment table.
+    Fragment,
+}
+
+/// How many times each symbol is used, and what kind of symbol it is.
+pub type SymbolCounts = BTreeMap<CCode, (u64, SymbolKind)>;
+
 #[derive(Debug, Eq, PartialEq)]
 enum HuffmanNode {
     Leaf {
-        count: usize,
+        count: u64,
         key: CCode,
-        is_mod: bool,
+        kind: SymbolKind,
     },
     Branch {
         left: Box<HuffmanNode>,
@@ -30,6 +42,10 @@ enum HuffmanNode {
 }
 
 impl HuffmanEntry {
+    pub fn new(bits: BitVec<u8>, kind: SymbolKind) -> Self {
+        Self { kind, bits }
+    }
+
     pub fn bits(&self) -> &BitVec<u8> {
         &self.bits
     }
@@ -40,9 +56,9 @@ impl HuffmanEntry {
 }
 
 impl HuffmanTable {
-    pub fn new(keys: Vec<KeyPress>) -> Result<Self, Error> {
-        assert!(!keys.is_empty());
-        let counts = count(keys);
+    /// Make codes for symbols of any kind, given how often each one is used.
+    pub fn from_counts(counts: SymbolCounts) -> Result<Self, Error> {
+        assert!(!counts.is_empty());
         let tree = make_tree(counts).expect("failed to make huffman tree");
         let mut map = BTreeMap::new();
         make_codes(&tree, BitVec::default(), &mut map)?;
@@ -62,6 +78,58 @@ impl HuffmanTable {
         })
     }
 
+    /// Decode a huffman-encoded sequence, the same way the firmware does.
+    /// Modifiers accumulate until a key (or blank key) ends the keypress. The
+    /// given function expands any fragment codes.
+    pub fn decode<F>(
+        &self,
+        bits: &BitVec<u8>,
+        mut expand_fragment: F,
+    ) -> Result<Sequence, Error>
+    where
+        F: FnMut(&CCode) -> Result<Sequence, Error>,
+    {
+        let mut seq = Sequence::default();
+        let mut keypress = KeyPress::default();
+        let mut start = 0;
+        while start < bits.len() {
+            let (code, entry) = self
+                .0
+                .iter()
+                .find(|(_, entry)| has_prefix_at(bits, start, &entry.bits))
+                .ok_or_else(|| Error::LookupErr {
+                    key: format!("bits starting at index {}", start),
+                    container: "huffman code table".into(),
+                })?;
+            start += entry.num_bits();
+
+            match entry.kind {
+                SymbolKind::Mod => keypress.mods.push(code.to_owned()),
+                SymbolKind::Key => {
+                    if *code != KeyPress::blank() {
+                        keypress.key = Some(code.to_owned());
+                    }
+                    seq.push(keypress);
+                    keypress = KeyPress::default();
+                }
+                SymbolKind::Fragment => {
+                    if !keypress.mods.is_empty() {
+                        return Err(Error::BadValueErr {
+                            thing: "huffman sequence".into(),
+                            value: "modifier before a fragment".into(),
+                        });
+                    }
+                    seq.0.extend(expand_fragment(code)?.0);
+                }
+            }
+        }
+        if !keypress.mods.is_empty() {
+            return Err(Error::Empty("key at end of huffman sequence".into()))
+                .context("Failed to decode huffman sequence");
+        }
+        Ok(seq)
+    }
+
     pub fn min_length(&self) -> usize {
         self.0
             .values()
@@ -80,7 +148,7 @@ impl HuffmanTable {
 }
 
 impl HuffmanNode {
-    fn count(&self) -> usize {
+    fn count(&self) -> u64 {
         match self {
             HuffmanNode::Leaf { count, .. } => *count,
             HuffmanNode::Branch { left, right } => left.count() + right.count(),
@@ -119,14 +187,12 @@ fn make_codes(
             right_prefix.push(true);
             make_codes(&right, right_prefix, out)?;
         }
-        HuffmanNode::Leaf {
-            ref key, is_mod, ..
-        } => {
+        HuffmanNode::Leaf { ref key, kind, .. } => {
             out.insert(
                 key.to_owned(),
                 HuffmanEntry {
                     bits: prefix,
-                    is_mod: *is_mod,
+                    kind: *kind,
                 },
             );
         }
@@ -134,10 +200,15 @@ fn make_codes(
     Ok(())
 }
 
-fn make_tree(counts: BTreeMap<CCode, (usize, bool)>) -> Option<HuffmanNode> {
+fn has_prefix_at(bits: &BitVec<u8>, start: usize, prefix: &BitVec<u8>) -> bool {
+    start + prefix.len() <= bits.len()
+        && prefix.iter().enumerate().all(|(i, b)| bits[start + i] == b)
+}
+
+fn make_tree(counts: SymbolCounts) -> Option<HuffmanNode> {
     let mut queue = BinaryHeap::new();
-    for (key, (count, is_mod)) in counts {
-        queue.push(HuffmanNode::Leaf { key, is_mod, count });
+    for (key, (count, kind)) in counts {
+        queue.push(HuffmanNode::Leaf { key, kind, count });
     }
     while queue.len() >= 2 {
         let left = queue.pop().unwrap();
@@ -150,24 +221,26 @@ fn make_tree(counts: BTreeMap<CCode, (usize, bool)>) -> Option<HuffmanNode> {
     queue.pop()
 }
 
-fn count(keys: Vec<KeyPress>) -> BTreeMap<CCode, (usize, bool)> {
-    let mut counts: BTreeMap<CCode, (usize, bool)> = BTreeMap::new();
-    for keypress in keys {
-        increment(&mut counts, keypress.key_or_blank(), false);
-        for modifier in keypress.mods {
-            increment(&mut counts, modifier, true);
-        }
+/// Count the key and modifiers of the keypress the given number of times.
+pub fn count_keypress(
+    counts: &mut SymbolCounts,
+    keypress: &KeyPress,
+    weight: u64,
+) {
+    increment(counts, keypress.key_or_blank(), SymbolKind::Key, weight);
+    for modifier in &keypress.mods {
+        increment(counts, modifier.to_owned(), SymbolKind::Mod, weight);
     }
-    counts
 }
-// TODO closure
-fn increment(
-    map: &mut BTreeMap<CCode, (usize, bool)>,
+
+pub fn increment(
+    counts: &mut SymbolCounts,
     key: CCode,
-    is_mod: bool,
+    kind: SymbolKind,
+    weight: u64,
 ) {
-    let count = map.entry(key).or_insert((0, is_mod));
-    (*count).0 += 1;
+    let count = counts.entry(key).or_insert((0, kind));
+    (*count).0 += weight;
 }
 
 // #[test]
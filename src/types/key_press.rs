@@ -11,7 +11,7 @@ pub const BLANK_KEY: &str =
 
 // TODO KeyPress is also used to store command codes, which is kinda a hack.
 // Rename?
-#[derive(Debug, PartialEq, Eq, Clone, Deserialize)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct KeyPress {
     pub key: Option<CCode>,
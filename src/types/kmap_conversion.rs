@@ -0,0 +1,99 @@
+use error::{Error, ResultExt};
+use types::{Chord, KmapFormat, KmapOrder, Permutation, SwitchPos, Validate};
+
+/// Describes how to move kmaps to a keyboard with a different `kmap_format`,
+/// like one with a different matrix or number of switches.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct KmapConversion {
+    /// The `kmap_format` of the new keyboard.
+    pub kmap_format: KmapFormat,
+    /// Pairs of corresponding switches, from the old `kmap_format` to the new
+    /// one. Old switches that aren't listed don't exist on the new keyboard,
+    /// and new switches that aren't listed are never pressed.
+    pub switches: Vec<(SwitchPos, SwitchPos)>,
+}
+
+/// Converts chords from the old `kmap_format` to the new one.
+#[derive(Debug)]
+pub struct KmapConverter {
+    /// Indices of the old switches that exist on the new keyboard, in kmap
+    /// order.
+    kept: Vec<usize>,
+    /// Indices of the old switches that don't exist on the new keyboard.
+    dropped: Vec<usize>,
+    /// From the kept switches to the new kmap order.
+    permutation: Permutation,
+}
+
+impl KmapConversion {
+    pub fn converter(
+        &self,
+        old_format: &KmapFormat,
+    ) -> Result<KmapConverter, Error> {
+        let old_order = old_format.kmap_order();
+        let mut kept = Vec::new();
+        let mut kept_targets = Vec::new();
+        for (old_switch, new_switch) in &self.switches {
+            let index = old_order
+                .iter()
+                .position(|switch| switch == old_switch)
+                .ok_or_else(|| Error::LookupErr {
+                    key: old_switch.to_string(),
+                    container: "the current kmap_format".to_owned(),
+                })?;
+            if kept_targets.contains(new_switch) {
+                return Err(Error::ConflictErr {
+                    key: new_switch.to_string(),
+                    container: "switches of the kmap conversion".to_owned(),
+                });
+            }
+            kept.push(index);
+            kept_targets.push(*new_switch);
+        }
+        let dropped =
+            (0..old_order.len()).filter(|i| !kept.contains(i)).collect();
+
+        let permutation =
+            Permutation::from_to(&kept_targets, &self.kmap_format.kmap_order())
+                .context(
+                    "The kmap conversion contains switches that are not \
+                     present in the new kmap_format",
+                )?;
+        Ok(KmapConverter {
+            kept,
+            dropped,
+            permutation,
+        })
+    }
+}
+
+impl Validate for KmapConversion {
+    fn validate(&self) -> Result<(), Error> {
+        self.kmap_format.validate()?;
+        for (old_switch, new_switch) in &self.switches {
+            old_switch.validate()?;
+            new_switch.validate()?;
+        }
+        Ok(())
+    }
+}
+
+impl KmapConverter {
+    /// Convert the chord to the new `kmap_format`, or return None if it uses
+    /// switches that don't exist on the new keyboard.
+    pub fn convert(
+        &self,
+        chord: &Chord<KmapOrder>,
+    ) -> Result<Option<Chord<KmapOrder>>, Error> {
+        let switches = chord.switches();
+        if self.dropped.iter().any(|&i| switches[i]) {
+            return Ok(None);
+        }
+        let kept = self.kept.iter().map(|&i| switches[i]).collect();
+        let mut converted =
+            Chord::from_vec(kept)?.permute(&self.permutation)?;
+        converted.anagram_num = chord.anagram_num;
+        Ok(Some(converted))
+    }
+}
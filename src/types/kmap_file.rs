@@ -0,0 +1,124 @@
+use error::Error;
+use types::{Chord, KmapConverter, KmapFormat, KmapOrder, Name, Permutation};
+
+/// The full contents of a kmap file, including comments, so that it can be
+/// written back out after being formatted or edited.
+#[derive(Debug, Clone)]
+pub struct KmapFile {
+    pub layout: KmapLayout,
+    pub items: Vec<KmapItem>,
+}
+
+#[derive(Debug, Clone)]
+pub enum KmapItem {
+    /// Consecutive comment lines, including their comment characters.
+    Comment(Vec<String>),
+    Section(KmapSection),
+}
+
+/// A line of chord names, followed by the chords themselves.
+#[derive(Debug, Clone, Default)]
+pub struct KmapSection {
+    pub names: Vec<Name>,
+    pub chords: Vec<Chord<KmapOrder>>,
+}
+
+/// How the switches of a single chord are spaced out on each line, written as
+/// an unpressed chord (like `....   ....`). Leading whitespace is kept, since
+/// it's often used to line up thumb switches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KmapLayout(pub Vec<String>);
+
+impl KmapFile {
+    pub const COMMENT_START: char = '#';
+    pub const UNPRESSED_CHAR: char = '.';
+    pub const PRESSED_CHAR: char = '*';
+    /// A placeholder name for blocks that don't map to anything.
+    pub const BLANK_MAPPING: &str = "blank_mapping";
+
+    /// Rearrange the switches of every chord, keeping the names, comments and
+    /// layout.
+    pub fn transform(&self, permutation: &Permutation) -> Result<Self, Error> {
+        let items = self
+            .items
+            .iter()
+            .map(|item| match item {
+                KmapItem::Comment(lines) => {
+                    Ok(KmapItem::Comment(lines.clone()))
+                }
+                KmapItem::Section(section) => {
+                    Ok(KmapItem::Section(KmapSection {
+                        names: section.names.clone(),
+                        chords: section
+                            .chords
+                            .iter()
+                            .map(|chord| chord.permute(permutation))
+                            .collect::<Result<_, _>>()?,
+                    }))
+                }
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(KmapFile {
+            layout: self.layout.clone(),
+            items,
+        })
+    }
+
+    /// Convert every chord to a new `kmap_format`, using a plain layout.
+    /// Chords that can't be represented in the new format are left out, and
+    /// their names are returned.
+    pub fn convert(
+        &self,
+        converter: &KmapConverter,
+        new_format: &KmapFormat,
+    ) -> Result<(Self, Vec<Name>), Error> {
+        let mut items = Vec::new();
+        let mut unconverted = Vec::new();
+        for item in &self.items {
+            let section = match item {
+                KmapItem::Comment(_) => {
+                    items.push(item.clone());
+                    continue;
+                }
+                KmapItem::Section(section) => section,
+            };
+            let mut converted = KmapSection::default();
+            for (name, chord) in section.names.iter().zip(&section.chords) {
+                match converter.convert(chord)? {
+                    Some(chord) => {
+                        converted.names.push(name.to_owned());
+                        converted.chords.push(chord);
+                    }
+                    None => unconverted.push(name.to_owned()),
+                }
+            }
+            if !converted.names.is_empty() {
+                items.push(KmapItem::Section(converted));
+            }
+        }
+        let file = KmapFile {
+            layout: KmapLayout::plain(new_format),
+            items,
+        };
+        Ok((file, unconverted))
+    }
+}
+
+impl KmapLayout {
+    /// A layout with no spaces between switches, for when there's no existing
+    /// kmap file to copy the layout from.
+    pub fn plain(format: &KmapFormat) -> Self {
+        KmapLayout(
+            format
+                .switches_per_line()
+                .into_iter()
+                .map(|len| KmapFile::UNPRESSED_CHAR.to_string().repeat(len))
+                .collect(),
+        )
+    }
+
+    /// The width of the widest line.
+    pub fn width(&self) -> usize {
+        self.0.iter().map(|line| line.len()).max().unwrap_or(0)
+    }
+}
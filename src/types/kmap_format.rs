@@ -1,7 +1,7 @@
 use std::fmt::{self, Debug};
 
 use error::Error;
-use types::Validate;
+use types::{BoardName, Validate};
 
 #[derive(Debug, PartialEq, Eq, Clone, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -88,17 +88,19 @@ impl fmt::Display for SwitchPos {
 impl Validate for Pin {
     #[allow(clippy::absurd_extreme_comparisons)]
     fn validate(&self) -> Result<(), Error> {
+        // This only checks that the pin exists on some board. Pins are checked
+        // against the selected board in `UserOptions::validate_board()`.
         const MIN_PIN_NUM: u8 = 0;
-        const MAX_PIN_NUM: u8 = 30; // TODO pick real value
+        let max_pin_num = BoardName::max_pin_num();
 
-        if self.0 <= MAX_PIN_NUM && self.0 >= MIN_PIN_NUM {
+        if self.0 <= max_pin_num && self.0 >= MIN_PIN_NUM {
             Ok(())
         } else {
             Err(Error::OutOfRangeErr {
                 name: "pin number".into(),
                 value: self.0 as usize,
                 min: MIN_PIN_NUM as usize,
-                max: MAX_PIN_NUM as usize,
+                max: max_pin_num as usize,
             })
         }
     }
@@ -3,35 +3,17 @@
 use std::collections::BTreeMap;
 use std::fmt::Debug;
 
-use error::Error;
 use types::{
-    CCode, Chord, ChordSpec, KmapOrder, ModeName, Name, SpellingTable, ToC,
-    Validate,
+    Chord, ChordSpec, KmapOrder, ModeName, Name, SourceSpan, SpellingTable,
+    Word, WordSpacePosition,
 };
 
-#[derive(Deserialize, Debug, Clone, Copy)]
-pub enum BoardName {
-    #[serde(rename = "TEENSY_LC")]
-    TeensyLC,
-}
-
 #[derive(Debug, Clone)]
 pub struct TutorData {
     pub chords: BTreeMap<ModeName, BTreeMap<Name, Chord<KmapOrder>>>,
+    pub spans: BTreeMap<ModeName, BTreeMap<Name, SourceSpan>>,
     pub spellings: SpellingTable,
     pub chord_spec: ChordSpec,
-}
-
-impl ToC for BoardName {
-    fn to_c(self) -> CCode {
-        match self {
-            BoardName::TeensyLC => "TEENSY_LC".to_c(),
-        }
-    }
-}
-
-impl Validate for BoardName {
-    fn validate(&self) -> Result<(), Error> {
-        Ok(())
-    }
+    pub dictionary: Vec<Word>,
+    pub word_space_position: WordSpacePosition,
 }
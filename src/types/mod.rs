@@ -2,40 +2,56 @@ pub(crate) use self::all_data::{
     AllChordMaps, AllData, AllSeqMaps, ChordMap, SeqMap,
 };
 // TODO move to output?
+pub(crate) use self::board::BoardName;
 pub(crate) use self::c_code::{CCode, CEnumVariant, CTree, Field, ToC};
 pub(crate) use self::c_enums::{Command, ModeName, Modifier, SeqType};
 pub(crate) use self::chord::{Chord, ChordSpec, FirmwareOrder, KmapOrder};
-pub(crate) use self::huffman::{HuffmanEntry, HuffmanTable};
+pub(crate) use self::diagnostic::{
+    Diagnostic, DiagnosticFormat, DiagnosticKind, Severity,
+};
+pub(crate) use self::encoder::{SeqToken, SequenceEncoder, SequenceEncoding};
+pub(crate) use self::huffman::{HuffmanEntry, HuffmanTable, SymbolKind};
 pub(crate) use self::key_press::{KeyDefs, KeyPress};
+pub(crate) use self::kmap_conversion::{KmapConversion, KmapConverter};
+pub(crate) use self::kmap_file::{KmapFile, KmapItem, KmapLayout, KmapSection};
 pub(crate) use self::kmap_format::{KmapFormat, Pin, SwitchPos};
-pub(crate) use self::miscellaneous::{BoardName, TutorData};
+pub(crate) use self::miscellaneous::TutorData;
 pub(crate) use self::mode_info::{KmapPath, ModeInfo};
 pub(crate) use self::name::Name;
 pub(crate) use self::permutation::Permutation;
 pub(crate) use self::sequence::Sequence;
+pub(crate) use self::source_span::SourceSpan;
 pub(crate) use self::spelling::{Spelling, SpellingTable};
 pub(crate) use self::user_options::{
-    Delay, UserOptions, Verbosity, WordSpacePosition,
+    ArduinoBackend, Delay, UserOptions, Verbosity, WordSpacePosition,
 };
 pub(crate) use self::validate::Validate;
+pub(crate) use self::word_frequencies::WordFrequencies;
 pub(crate) use self::words::{AnagramNum, Snippet, Word, Wordlike};
 
 #[macro_use]
 mod validate;
 
 mod all_data;
+mod board;
 mod c_code;
 mod c_enums;
 mod checker;
 mod chord;
+mod diagnostic;
+mod encoder;
 mod huffman;
 mod key_press;
+mod kmap_conversion;
+mod kmap_file;
 mod kmap_format;
 mod miscellaneous;
 mod mode_info;
 mod name;
 mod permutation;
 mod sequence;
+mod source_span;
 mod spelling;
 mod user_options;
+mod word_frequencies;
 mod words;
@@ -19,7 +19,9 @@ pub struct KmapInfo {
     pub use_words: bool,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Deserialize)]
+#[derive(
+    Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Deserialize, Serialize,
+)]
 #[serde(deny_unknown_fields)]
 pub struct KmapPath(pub String);
 
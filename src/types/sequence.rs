@@ -9,20 +9,10 @@ use unicode_segmentation::UnicodeSegmentation;
 use error::{Error, ResultExt};
 use types::{KeyPress, Validate};
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct Sequence(pub Vec<KeyPress>);
 
 impl Sequence {
-    /// Flatten a list of sequences into a single sequence containing all of
-    /// their keypresses.
-    pub fn flatten(seqs: &[&Self]) -> Self {
-        Sequence(
-            seqs.iter()
-                .flat_map(|seq| seq.keypresses().cloned())
-                .collect(),
-        )
-    }
-
     pub fn push(&mut self, keypress: KeyPress) {
         self.0.push(keypress)
     }
@@ -0,0 +1,19 @@
+use std::fmt;
+
+use types::KmapPath;
+
+/// Where a chord was defined in a kmap file. The line is the one containing
+/// the chord's name, and the block is the chord's position along that line,
+/// starting from 1.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SourceSpan {
+    pub kmap: KmapPath,
+    pub line: usize,
+    pub block: usize,
+}
+
+impl fmt::Display for SourceSpan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{} (block {})", self.kmap, self.line, self.block)
+    }
+}
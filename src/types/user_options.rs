@@ -1,14 +1,20 @@
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 use error::{Error, ResultExt};
 use types::{
-    BoardName, ChordSpec, KmapFormat, Permutation, Pin, SwitchPos, Validate,
+    BoardName, ChordSpec, KmapFormat, Name, Permutation, Pin, SequenceEncoding,
+    SwitchPos, Validate,
 };
 
 fn default_output_dir() -> PathBuf {
     PathBuf::from("pipit-firmware")
 }
 
+fn default_firmware_source_dir() -> PathBuf {
+    PathBuf::from("pipit-firmware")
+}
+
 validated_struct! {
     #[derive(Deserialize, Debug)]
     #[serde(deny_unknown_fields)]
@@ -35,8 +41,38 @@ validated_struct! {
         #[serde(default = "return_false")]
         pub enable_audio_typing_feedback: bool,
 
-        #[serde(default = "return_true")]
-        pub use_standby_interrupts: bool,
+        #[serde(default)]
+        pub arduino_backend: ArduinoBackend,
+
+        /// Defaults to `arduino` or `arduino-cli`, depending on the backend.
+        pub arduino_executable: Option<PathBuf>,
+
+        /// Defaults to the output directory.
+        pub sketch_directory: Option<PathBuf>,
+
+        /// The checked-in firmware sources that `--export` copies.
+        #[serde(default = "default_firmware_source_dir")]
+        pub firmware_source_directory: PathBuf,
+
+        /// Rearrangements of the switches in `kmap_format`, for deriving
+        /// kmaps like mirrored layouts from existing ones.
+        #[serde(default)]
+        pub kmap_transforms: BTreeMap<Name, KmapFormat>,
+
+        /// How to compress the sequences in the firmware lookup tables.
+        #[serde(default)]
+        pub sequence_encoding: SequenceEncoding,
+
+        /// A plain text file, like a corpus or a log of typed text. Words
+        /// that are common in it get shorter huffman codes.
+        pub huffman_corpus: Option<PathBuf>,
+
+        /// The maximum estimated size of the lookup tables, in bytes.
+        pub memory_budget: Option<usize>,
+
+        /// Defaults to whether the board supports it.
+        #[serde(default)]
+        pub use_standby_interrupts: Option<bool>,
     }
 }
 
@@ -58,6 +94,15 @@ always_valid_enum! {
     }
 }
 
+always_valid_enum! {
+    #[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum ArduinoBackend {
+        #[default]
+        ArduinoIde,
+        ArduinoCli,
+    }
+}
+
 #[derive(Deserialize, Debug, Clone, Copy)]
 #[serde(deny_unknown_fields)]
 pub struct Delay(pub u16);
@@ -80,6 +125,73 @@ impl UserOptions {
         })
     }
 
+    /// Check that all the pins exist on the selected board, and that it
+    /// supports the enabled features.
+    pub fn validate_board(&self) -> Result<(), Error> {
+        let board = self.board_name;
+        let info = board.info();
+        let switch_pins = self
+            .kmap_format
+            .kmap_order()
+            .into_iter()
+            .flat_map(|switch_pos| vec![switch_pos.row, switch_pos.col]);
+        for pin in self
+            .row_pins
+            .iter()
+            .chain(&self.column_pins)
+            .cloned()
+            .chain(switch_pins)
+        {
+            board.validate_pin(pin)?;
+        }
+
+        if let Some(ref pins) = self.rgb_led_pins {
+            for &pin in pins {
+                board.validate_pin(pin)?;
+            }
+        }
+        if self.use_standby_interrupts == Some(true) {
+            board.ensure_supports(
+                "use_standby_interrupts",
+                info.standby_interrupts,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Look up a transform from `kmap_transforms`. Each switch position in the
+    /// transform says which switch of the original chord to move to that
+    /// position, so it must list every switch in `kmap_format` exactly once.
+    pub fn kmap_transform(&self, name: &str) -> Result<Permutation, Error> {
+        let transform = self
+            .kmap_transforms
+            .get(&Name(name.to_owned()))
+            .ok_or_else(|| Error::LookupErr {
+                key: name.to_owned(),
+                container: "kmap_transforms".to_owned(),
+            })?;
+        let context = || {
+            format!(
+                "kmap transform '{}' must contain each switch in \
+                 'kmap_format' exactly once",
+                name
+            )
+        };
+        if transform.num_switches() != self.kmap_format.num_switches() {
+            return Err(Error::PermuteLength).with_context(context);
+        }
+        Permutation::from_to(
+            &self.kmap_format.kmap_order(),
+            &transform.kmap_order(),
+        )
+        .with_context(context)
+    }
+
+    pub fn use_standby_interrupts(&self) -> bool {
+        self.use_standby_interrupts
+            .unwrap_or_else(|| self.board_name.info().standby_interrupts)
+    }
+
     /// The order in which the firmware will scan matrix positions while
     /// checking for pressed switches. It must match the algorithm used
     /// in the firmware's `scanMatrix()`!
@@ -100,6 +212,19 @@ impl UserOptions {
     }
 }
 
+impl ArduinoBackend {
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        match name {
+            "ide" => Ok(ArduinoBackend::ArduinoIde),
+            "cli" => Ok(ArduinoBackend::ArduinoCli),
+            _ => Err(Error::BadValueErr {
+                thing: "arduino backend".into(),
+                value: name.into(),
+            }),
+        }
+    }
+}
+
 impl Default for WordSpacePosition {
     fn default() -> Self {
         WordSpacePosition::Before
@@ -121,7 +246,3 @@ impl From<Delay> for usize {
 fn return_false() -> bool {
     false
 }
-
-fn return_true() -> bool {
-    true
-}
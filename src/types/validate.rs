@@ -79,6 +79,13 @@ impl Validate for bool {
     }
 }
 
+impl Validate for usize {
+    fn validate(&self) -> Result<(), Error> {
+        // Primitive types are always valid.
+        Ok(())
+    }
+}
+
 // impl Validate for String {
 //     fn validate(&self) -> Result<(), Error> {
 //         // Primitive types are always valid.
@@ -0,0 +1,42 @@
+use std::collections::BTreeMap;
+
+/// How common each word is, used to decide which of several words with the
+/// same chord should get the lowest anagram number. Words are stored in
+/// lowercase, since capitalization doesn't affect chords.
+#[derive(Debug, Clone, Default)]
+pub struct WordFrequencies(BTreeMap<String, u64>);
+
+impl WordFrequencies {
+    /// Make up frequencies from a list of words ordered from most to least
+    /// common. Only the order matters, not the exact values.
+    pub fn from_ranking<T>(words: T) -> Self
+    where
+        T: IntoIterator<Item = String>,
+    {
+        let words: Vec<_> = words.into_iter().collect();
+        let mut map = BTreeMap::new();
+        for (rank, word) in words.iter().enumerate() {
+            map.entry(word.to_lowercase())
+                .or_insert((words.len() - rank) as u64);
+        }
+        WordFrequencies(map)
+    }
+
+    /// Make frequencies from words and how often they occur. Counts for words
+    /// that only differ by capitalization are added together.
+    pub fn from_counts<T>(counts: T) -> Self
+    where
+        T: IntoIterator<Item = (String, u64)>,
+    {
+        let mut map = BTreeMap::new();
+        for (word, count) in counts {
+            *map.entry(word.to_lowercase()).or_insert(0) += count;
+        }
+        WordFrequencies(map)
+    }
+
+    /// Return the frequency of the word, or 0 if it's unknown.
+    pub fn get(&self, word: &str) -> u64 {
+        self.0.get(&word.to_lowercase()).cloned().unwrap_or(0)
+    }
+}
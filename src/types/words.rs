@@ -8,7 +8,7 @@ use types::{KeyPress, Name, SeqType, Sequence, Spelling, Validate};
 
 const DEFAULT_ANAGRAM_NUM: u8 = 0;
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(deny_unknown_fields)]
 pub struct Word {
     pub word: String,